@@ -1,9 +1,14 @@
 {
-  telegram = {
-    bot_token = "test_token";
-    alerts_chat = "test_chat_id";
+  alerts = {
+    sinks = [
+      {
+        type = "telegram";
+        bot_token = "test_token";
+        alerts_chat = "test_chat_id";
+      }
+    ];
   };
   monitor = {
-    max_size = "5GB";
+    watched_dirs = [ { path = "~/.local/state"; max_size = "5GB"; } ];
   };
 }
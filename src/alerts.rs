@@ -0,0 +1,248 @@
+//! Alert lifecycle shared by all checks: checks report [Observation]s, [Alerter] decides when they warrant a notification.
+use std::{
+	collections::VecDeque,
+	sync::Mutex,
+	time::{Duration, SystemTime},
+};
+
+use color_eyre::eyre::Result;
+use tracing::info;
+
+use crate::{
+	notify::{Alert, Notifiers, Severity},
+	state::{Incident, Sample, StateStore, Status},
+};
+
+/// Single measurement of a check, already evaluated against its thresholds
+#[derive(Clone, Debug)]
+pub struct Observation {
+	/// Check ID (e.g. "disk:/")
+	pub check: String,
+	/// What is measured, for composing messages (e.g. "/ disk usage")
+	pub subject: String,
+	/// Used for tracking the peak; higher is worse
+	pub value: u64,
+	/// `value`, human-readable (e.g. "91%")
+	pub display: String,
+	pub evaluation: Evaluation,
+	/// Remind about a still-firing incident this often
+	pub renotify: Option<Duration>,
+	/// Extra details appended to firing alerts (e.g. projected time until full)
+	pub context: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Evaluation {
+	/// Over a threshold tier
+	Firing {
+		/// Rank of the tier crossed; higher is worse. Crossing a higher tier than already alerted for alerts again.
+		tier: u32,
+		severity: Severity,
+		/// Human-readable threshold of that tier. None for conditions that have no threshold to cross (e.g. a unit having failed), which words alerts as "{subject} is {display}".
+		threshold: Option<String>,
+	},
+	/// Between reset and first tier: an ongoing incident is neither escalated nor resolved
+	Hold,
+	/// Below the reset threshold
+	Clear { threshold: Option<String> },
+}
+
+#[derive(Debug)]
+pub struct Alerter {
+	notifiers: Notifiers,
+	store: Mutex<StateStore>,
+}
+
+impl Alerter {
+	pub fn new(notifiers: Notifiers, store: StateStore) -> Self {
+		Self {
+			notifiers,
+			store: Mutex::new(store),
+		}
+	}
+
+	/// Records `obs` and notifies if it starts, escalates or resolves an incident, or a reminder is due.
+	///
+	/// State only advances once the notification is delivered, so a failed send is retried on the next observation.
+	pub async fn observe(&self, obs: Observation) -> Result<()> {
+		let Some(alert) = self.pending_alert(&obs)? else {
+			return Ok(());
+		};
+
+		self.notifiers.notify(&alert).await?;
+
+		let mut store = self.store.lock().unwrap();
+		let state = store.check_mut(&obs.check);
+		let now = SystemTime::now();
+		match obs.evaluation {
+			Evaluation::Firing { tier, severity, .. } => {
+				let incident = state.incident.get_or_insert_with(|| Incident {
+					started: now,
+					severity,
+					tier,
+					peak: obs.value,
+					peak_display: obs.display.clone(),
+					last_notified: now,
+				});
+				incident.severity = severity;
+				incident.tier = incident.tier.max(tier);
+				incident.last_notified = now;
+				state.status = Status::Firing;
+			}
+			Evaluation::Clear { .. } => {
+				state.incident = None;
+				state.status = Status::Resolved;
+				info!("`{}` resolved, cleared alert state", obs.check);
+			}
+			Evaluation::Hold => unreachable!("holding never produces an alert"),
+		}
+		state.record(&alert);
+		store.save()
+	}
+
+	/// Notifies of a one-off occurrence (e.g. an OOM kill), which has no ongoing state to escalate or resolve
+	pub async fn event(&self, check: &str, severity: Severity, value: &str, message: String) -> Result<()> {
+		let alert = Alert::new(check, severity, value, "", message);
+		self.notifiers.notify(&alert).await?;
+
+		let mut store = self.store.lock().unwrap();
+		let state = store.check_mut(check);
+		state.last_value = Some(value.to_owned());
+		state.last_checked = Some(SystemTime::now());
+		state.record(&alert);
+		store.save()
+	}
+
+	/// Whether observing `obs` would send a firing alert: a new incident, a higher tier, or a reminder. For attaching details that are expensive to gather only when they'll be seen.
+	pub fn will_fire(&self, obs: &Observation) -> bool {
+		let Evaluation::Firing { tier, .. } = obs.evaluation else {
+			return false;
+		};
+		let store = self.store.lock().unwrap();
+		match store.get(&obs.check).and_then(|s| s.incident.as_ref()) {
+			None => true,
+			Some(incident) => tier > incident.tier || incident.should_renotify(obs.renotify),
+		}
+	}
+
+	/// IDs of the checks starting with `prefix` that have an ongoing incident, for resolving those whose subject went away
+	pub fn firing(&self, prefix: &str) -> Vec<String> {
+		let store = self.store.lock().unwrap();
+		store
+			.checks()
+			.filter(|(check, state)| check.starts_with(prefix) && state.incident.is_some())
+			.map(|(check, _)| check.to_owned())
+			.collect()
+	}
+
+	/// Updates the bookkeeping of `obs.check`, and composes the alert `obs` calls for, if any
+	fn pending_alert(&self, obs: &Observation) -> Result<Option<Alert>> {
+		let mut store = self.store.lock().unwrap();
+		let state = store.check_mut(&obs.check);
+		state.last_value = Some(obs.display.clone());
+		state.last_checked = Some(SystemTime::now());
+		if let Some(incident) = &mut state.incident
+			&& obs.value > incident.peak
+		{
+			incident.peak = obs.value;
+			incident.peak_display = obs.display.clone();
+		}
+
+		let alert = match (&obs.evaluation, &state.incident) {
+			(Evaluation::Clear { threshold }, Some(incident)) => {
+				let mut message = format!("{} back to {} after {}", obs.subject, obs.display, format_duration(incident.duration()));
+				if threshold.is_some() {
+					message = format!("{message} (peaked at {})", incident.peak_display);
+				}
+				Some(Alert::new(&obs.check, incident.severity, &obs.display, threshold.as_deref().unwrap_or_default(), message).resolved())
+			}
+			// new, or escalated to a higher tier
+			(Evaluation::Firing { tier, severity, threshold }, incident) if incident.as_ref().is_none_or(|incident| *tier > incident.tier) => {
+				let message = match threshold {
+					None => format!("{} is {}", obs.subject, obs.display),
+					Some(threshold) => format!("{} at {} (crossed {threshold} threshold)", obs.subject, obs.display),
+				};
+				Some(Alert::new(
+					&obs.check,
+					*severity,
+					&obs.display,
+					threshold.as_deref().unwrap_or_default(),
+					with_context(message, obs),
+				))
+			}
+			(Evaluation::Firing { severity, threshold, .. }, Some(incident)) if incident.should_renotify(obs.renotify) => {
+				let message = match threshold {
+					None => format!("{} still {} after {}", obs.subject, obs.display, format_duration(incident.duration())),
+					Some(threshold) => format!("{} still at {}, over {threshold} for {}", obs.subject, obs.display, format_duration(incident.duration())),
+				};
+				Some(Alert::new(
+					&obs.check,
+					*severity,
+					&obs.display,
+					threshold.as_deref().unwrap_or_default(),
+					with_context(message, obs),
+				))
+			}
+			_ => None,
+		};
+
+		store.save_debounced()?;
+		Ok(alert)
+	}
+
+	/// Records a sample of `check`'s `value`, and fits its growth rate (units per second) over the last `window`
+	pub fn record_sample(&self, check: &str, value: u64, window: Duration) -> Result<Option<f64>> {
+		let mut store = self.store.lock().unwrap();
+		let state = store.check_mut(check);
+		state.record_sample(value, window);
+		let rate = growth_rate(&state.samples);
+		store.save_debounced()?;
+		Ok(rate)
+	}
+}
+
+fn with_context(message: String, obs: &Observation) -> String {
+	match &obs.context {
+		Some(context) => format!("{message}\n{context}"),
+		None => message,
+	}
+}
+
+/// Fewer samples than this don't make a trend
+const MIN_SAMPLES: usize = 3;
+
+/// Least-squares slope of the samples, in units per second
+pub fn growth_rate(samples: &VecDeque<Sample>) -> Option<f64> {
+	if samples.len() < MIN_SAMPLES {
+		return None;
+	}
+	let t0 = samples.front()?.at;
+	let points: Vec<(f64, f64)> = samples.iter().map(|s| (s.at.duration_since(t0).unwrap_or_default().as_secs_f64(), s.value as f64)).collect();
+	let n = points.len() as f64;
+	let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+	let mean_v = points.iter().map(|(_, v)| v).sum::<f64>() / n;
+	let covariance: f64 = points.iter().map(|(t, v)| (t - mean_t) * (v - mean_v)).sum();
+	let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+	match variance > 0.0 {
+		true => Some(covariance / variance),
+		false => None,
+	}
+}
+
+/// How long until `remaining` is used up at `rate` per second; None if not growing
+pub fn time_to_full(remaining: u64, rate: Option<f64>) -> Option<Duration> {
+	let rate = rate.filter(|&r| r > 0.0)?;
+	// growing slowly enough, that's longer than a Duration can hold
+	Some(Duration::try_from_secs_f64(remaining as f64 / rate).unwrap_or(Duration::MAX))
+}
+
+/// Coarse human-readable form, e.g. "2d 3h", "3h 20m", "45m"
+pub fn format_duration(d: Duration) -> String {
+	let mins = d.as_secs() / 60;
+	let (days, hours, mins) = (mins / (60 * 24), mins / 60 % 24, mins % 60);
+	match (days, hours) {
+		(0, 0) => format!("{mins}m"),
+		(0, _) => format!("{hours}h {mins}m"),
+		_ => format!("{days}d {hours}h"),
+	}
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Result;
+use rayon::ThreadPool;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{error, info};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{
+	alerts::{Alerter, Evaluation, Observation, time_to_full},
+	checks::{check_eta, eta_display, join_context},
+	config::{MonitorConfig, ThrottleConfig, WatchedDir},
+	index::SizeIndex,
+	throttle::spawn_throttled,
+	walk::{WalkOptions, dir_usage},
+};
+
+/// Checks `dir` on its own schedule, forever. With `incremental`, sizes come from a [SizeIndex] instead of walking the tree each time.
+pub async fn watch_dir(config: MonitorConfig, throttle: ThrottleConfig, pool: Arc<ThreadPool>, alerter: Arc<Alerter>, dir: WatchedDir) {
+	let index = match dir.incremental {
+		true => match index_dir(&config, &throttle, &pool, &dir).await {
+			Ok(index) => Some(index),
+			Err(e) => {
+				error!("Failed to index {}, walking it on every check instead: {e}", dir.path);
+				None
+			}
+		},
+		false => None,
+	};
+
+	let mut check = tokio::time::interval(dir.interval.unwrap_or(config.interval));
+	check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	let mut reconcile = tokio::time::interval_at(Instant::now() + config.reconcile_interval, config.reconcile_interval);
+	reconcile.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	loop {
+		tokio::select! {
+			_ = check.tick() => {
+				if let Err(e) = check_watched_dir(&config, &throttle, &pool, &alerter, &dir, index.as_ref()).await {
+					error!("Failed to check size of {}: {e}", dir.path);
+				}
+			}
+			_ = reconcile.tick(), if index.is_some() => {
+				let index = index.clone().unwrap();
+				match spawn_throttled(&throttle, move || index.reconcile()).await {
+					Ok(drift) => info!("Reconciled size index of {}, it was off by {drift} bytes", dir.path),
+					Err(e) => error!("Failed to reconcile size index of {}: {e}", dir.path),
+				}
+			}
+		}
+	}
+}
+
+/// The index's event thread is spawned from the throttled one, so updates stay at the lowered priority too
+async fn index_dir(config: &MonitorConfig, throttle: &ThrottleConfig, pool: &Arc<ThreadPool>, dir: &WatchedDir) -> Result<SizeIndex> {
+	let path = dir.expanded_path()?;
+	let options = walk_options(config, throttle, pool, dir)?;
+	spawn_throttled(throttle, move || SizeIndex::watch(&path, &options)).await?
+}
+
+fn walk_options(config: &MonitorConfig, throttle: &ThrottleConfig, pool: &Arc<ThreadPool>, dir: &WatchedDir) -> Result<WalkOptions> {
+	Ok(WalkOptions {
+		top_n: config.top_consumers,
+		one_file_system: dir.one_file_system,
+		exclude: dir.exclude_set()?,
+		pool: Some(Arc::clone(pool)),
+		entries_per_sec: throttle.entries_per_sec,
+	})
+}
+
+/// Checks `dir` against its size budget; alert state is kept under [WatchedDir::check_id]
+pub async fn check_watched_dir(config: &MonitorConfig, throttle: &ThrottleConfig, pool: &Arc<ThreadPool>, alerter: &Alerter, dir: &WatchedDir, index: Option<&SizeIndex>) -> Result<()> {
+	let path = dir.expanded_path()?;
+	// walks of big trees take a while, and several directories may be due at once
+	let usage = match index {
+		Some(index) => {
+			let (index, top_n) = (index.clone(), config.top_consumers);
+			tokio::task::spawn_blocking(move || index.usage(top_n)).await?
+		}
+		None => {
+			let (path, options) = (path.clone(), walk_options(config, throttle, pool, dir)?);
+			spawn_throttled(throttle, move || dir_usage(&path, &options)).await??
+		}
+	};
+
+	let check = dir.check_id();
+	let size_bytes = usage.apparent;
+	let size = InfoSize::from_parts(size_bytes, InfoSizeUnit::Byte);
+	let allocated = InfoSize::from_parts(usage.allocated, InfoSizeUnit::Byte);
+	let reset_size = dir.reset.unwrap_or(dir.max_size);
+	let statvfs = nix::sys::statvfs::statvfs(&path)?;
+	let rate = alerter.record_sample(&check, size_bytes, config.eta_window)?;
+	let eta = time_to_full(statvfs.blocks_available() * statvfs.fragment_size(), rate);
+	info!(
+		"{} size: {size}, {allocated} on disk (threshold: {}), fills its filesystem in: {}",
+		dir.path,
+		dir.max_size,
+		eta_display(eta)
+	);
+
+	let evaluation = if size > dir.max_size {
+		Evaluation::Firing {
+			tier: 0,
+			severity: dir.severity,
+			threshold: Some(dir.max_size.to_string()),
+		}
+	} else if size < reset_size {
+		Evaluation::Clear {
+			threshold: Some(reset_size.to_string()),
+		}
+	} else {
+		Evaluation::Hold
+	};
+
+	let observation = Observation {
+		check: check.clone(),
+		subject: dir.path.clone(),
+		value: size_bytes,
+		display: format!("{size} ({allocated} on disk)"),
+		evaluation,
+		renotify: dir.renotify,
+		context: join_context([
+			eta.map(|_| format!("{} will fill its filesystem in {} at the current rate", dir.path, eta_display(eta))),
+			Some(usage.breakdown()),
+		]),
+	};
+	if let Err(e) = alerter.observe(observation).await {
+		error!("Failed to alert on {} size: {e}", dir.path);
+	}
+	check_eta(config, alerter, &check, &format!("{}'s filesystem", dir.path), eta).await
+}
@@ -0,0 +1,253 @@
+use std::{
+	collections::{HashMap, HashSet},
+	path::PathBuf,
+	sync::Arc,
+};
+
+use color_eyre::eyre::Result;
+use nix::sys::statvfs::{Statvfs, statvfs};
+use rayon::ThreadPool;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{
+	alerts::{Alerter, Evaluation, Observation, time_to_full},
+	checks::{check_eta, eta_display, evaluate_pct},
+	config::{FreeSpaceTier, MonitorConfig, MountConfig, ThrottleConfig},
+	mounts::{Mount, read_mounts},
+	notify::Severity,
+	throttle::spawn_throttled,
+	walk::{WalkOptions, dir_usage},
+};
+
+/// Work on mounts that outlives a round of [check_disk_usage], carried over between rounds
+#[derive(Debug, Default)]
+pub struct MountTasks {
+	/// Walks for the top space consumers of mounts that crossed a threshold, by mount point
+	walks: HashMap<PathBuf, JoinHandle<()>>,
+	/// statvfs calls that didn't return within `disk_stat_timeout`, by mount point. Each holds a blocking thread until it does, so no other is started for the mount meanwhile.
+	stats: HashMap<PathBuf, JoinHandle<nix::Result<Statvfs>>>,
+}
+
+/// Checks block usage, free space and inode usage of every monitored mount, each with its own thresholds and alert state
+pub async fn check_disk_usage(config: &MonitorConfig, throttle: &ThrottleConfig, pool: &Arc<ThreadPool>, alerter: &Arc<Alerter>, tasks: &mut MountTasks) -> Result<()> {
+	tasks.walks.retain(|_, walk| !walk.is_finished());
+	// one that finished since is of no use anymore; the mount gets a fresh one
+	tasks.stats.retain(|_, stat| !stat.is_finished());
+	for mount in monitored_mounts(config)? {
+		if let Err(e) = check_mount(config, throttle, pool, alerter, tasks, &mount).await {
+			error!("Failed to check disk usage of {}: {e}", mount.mount_point.display());
+		}
+	}
+	Ok(())
+}
+
+/// Local, real filesystems that aren't ignored by config, plus those explicitly included. A filesystem mounted in several places (bind mounts) is only checked once.
+fn monitored_mounts(config: &MonitorConfig) -> Result<Vec<Mount>> {
+	let mut mounts = read_mounts()?;
+	// prefer the mount of the filesystem's root over bind mounts of its subdirectories
+	mounts.sort_by_key(|m| m.root != "/");
+
+	let mut seen_devices = HashSet::new();
+	let mut monitored: Vec<Mount> = mounts
+		.into_iter()
+		.filter(|m| match config.disk_mounts.get(m.mount_point.to_string_lossy().as_ref()) {
+			Some(overrides) if overrides.ignore => false,
+			Some(overrides) if overrides.include => true,
+			_ => (config.disk_include_pseudo || !m.is_pseudo()) && !m.is_network() && !config.disk_ignore_fs_types.contains(&m.fs_type),
+		})
+		.filter(|m| seen_devices.insert(m.device.clone()))
+		.collect();
+	monitored.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+	Ok(monitored)
+}
+
+async fn check_mount(config: &MonitorConfig, throttle: &ThrottleConfig, pool: &Arc<ThreadPool>, alerter: &Arc<Alerter>, tasks: &mut MountTasks, mount: &Mount) -> Result<()> {
+	let mount_point = mount.mount_point.to_string_lossy();
+	let Some(statvfs) = stat_responsive(config, alerter, tasks, mount).await? else {
+		return Ok(());
+	};
+	let no_overrides = MountConfig::default();
+	let overrides = config.disk_mounts.get(mount_point.as_ref()).unwrap_or(&no_overrides);
+
+	let total_blocks = statvfs.blocks();
+	if total_blocks == 0 {
+		debug!("Skipping {mount_point}: reports no blocks");
+		return Ok(());
+	}
+	let available_blocks = statvfs.blocks_available();
+	let used_blocks = total_blocks - available_blocks;
+	let usage_pct = (used_blocks as f64 / total_blocks as f64 * 100.0) as u8;
+	let block_size = statvfs.fragment_size();
+	let rate = alerter.record_sample(&format!("disk:{mount_point}"), used_blocks * block_size, config.eta_window)?;
+	let eta = time_to_full(available_blocks * block_size, rate);
+	info!("{mount_point} disk usage: {usage_pct}%, full in: {}", eta_display(eta));
+
+	let disk_usage = Observation {
+		check: format!("disk:{mount_point}"),
+		subject: format!("{mount_point} disk usage"),
+		value: u64::from(usage_pct),
+		display: format!("{usage_pct}%"),
+		evaluation: evaluate_pct(
+			usage_pct,
+			config.disk_thresholds(overrides),
+			config.disk_reset(overrides),
+			overrides.severity.as_ref().unwrap_or(&config.disk_severity),
+		),
+		renotify: None,
+		context: eta.map(|_| format!("{mount_point} will be full in {} at the current rate", eta_display(eta))),
+	};
+	// walking a whole filesystem is expensive, so only done when someone is going to read the result
+	let breakdown = match disk_usage.evaluation {
+		Evaluation::Firing { severity, .. } if config.top_consumers > 0 && alerter.will_fire(&disk_usage) => Some((severity, disk_usage.display.clone())),
+		_ => None,
+	};
+	match alerter.observe(disk_usage).await {
+		Ok(()) =>
+			if let Some((severity, display)) = breakdown {
+				let options = WalkOptions {
+					top_n: config.top_consumers,
+					one_file_system: true,
+					pool: Some(Arc::clone(pool)),
+					entries_per_sec: throttle.entries_per_sec,
+					..Default::default()
+				};
+				tasks.spawn_breakdown(throttle, options, alerter, mount, severity, display);
+			},
+		Err(e) => error!("Failed to alert on {mount_point} disk usage: {e}"),
+	}
+	if let Err(e) = check_eta(config, alerter, &format!("disk:{mount_point}"), &mount_point, eta).await {
+		error!("Failed to alert on {mount_point} time until full: {e}");
+	}
+
+	let min_free = config.disk_min_free(overrides);
+	if !min_free.is_empty() {
+		let free_bytes = available_blocks * block_size;
+		let free = InfoSize::from_parts(free_bytes, InfoSizeUnit::Byte);
+		let free_space = Observation {
+			check: format!("disk_free:{mount_point}"),
+			subject: format!("{mount_point} free space"),
+			// lower free space is worse, so track the peak of what's taken instead
+			value: used_blocks * block_size,
+			display: free.to_string(),
+			evaluation: evaluate_free(free, min_free, config.disk_min_free_reset(overrides)),
+			renotify: None,
+			context: None,
+		};
+		if let Err(e) = alerter.observe(free_space).await {
+			error!("Failed to alert on {mount_point} free space: {e}");
+		}
+	}
+
+	// Running out of inodes makes a filesystem just as unusable as running out of blocks
+	match inode_usage(config, &mount_point, statvfs.files() - statvfs.files_free(), statvfs.files()) {
+		Some(inode_usage) => alerter.observe(inode_usage).await,
+		None => Ok(()),
+	}
+}
+
+/// Inode usage of `mount_point`, against its inode thresholds. None for filesystems that allocate inodes dynamically (btrfs) and report none.
+pub fn inode_usage(config: &MonitorConfig, mount_point: &str, used: u64, total: u64) -> Option<Observation> {
+	if total == 0 {
+		return None;
+	}
+	let no_overrides = MountConfig::default();
+	let overrides = config.disk_mounts.get(mount_point).unwrap_or(&no_overrides);
+	let pct = (used as f64 / total as f64 * 100.0) as u8;
+	info!("{mount_point} inode usage: {pct}%");
+	Some(Observation {
+		check: format!("inodes:{mount_point}"),
+		subject: format!("{mount_point} inode usage"),
+		value: u64::from(pct),
+		display: format!("{pct}%"),
+		evaluation: evaluate_pct(pct, config.inode_thresholds(overrides), config.inode_reset(overrides), &config.inode_severity),
+		renotify: None,
+		context: None,
+	})
+}
+
+impl MountTasks {
+	/// Walks `mount` for its top space consumers and sends them as a follow-up to its usage alert. In the background, as that can take hours on a big filesystem when throttled,
+	/// and neither the alert nor the other mounts should wait on it. A mount still being walked from an earlier alert isn't walked again.
+	fn spawn_breakdown(&mut self, throttle: &ThrottleConfig, options: WalkOptions, alerter: &Arc<Alerter>, mount: &Mount, severity: Severity, display: String) {
+		if self.walks.contains_key(&mount.mount_point) {
+			debug!("{} is still being walked for its top space consumers", mount.mount_point.display());
+			return;
+		}
+		let (throttle, alerter, path) = (throttle.clone(), Arc::clone(alerter), mount.mount_point.clone());
+		let walk = tokio::spawn(async move {
+			let mount_point = path.to_string_lossy().into_owned();
+			match spawn_throttled(&throttle, move || dir_usage(&path, &options)).await.and_then(|usage| usage) {
+				Ok(usage) => {
+					let message = format!("{mount_point} disk usage at {display}, top space consumers:\n{}", usage.breakdown());
+					if let Err(e) = alerter.event(&format!("disk_breakdown:{mount_point}"), severity, &display, message).await {
+						error!("Failed to send top space consumers of {mount_point}: {e}");
+					}
+				}
+				Err(e) => error!("Failed to find top space consumers of {mount_point}: {e}"),
+			}
+		});
+		self.walks.insert(mount.mount_point.clone(), walk);
+	}
+}
+
+/// statvfs of `mount`, alerting if it doesn't answer within `disk_stat_timeout` (as network filesystems do when their server is unreachable). None while it's unresponsive.
+async fn stat_responsive(config: &MonitorConfig, alerter: &Alerter, tasks: &mut MountTasks, mount: &Mount) -> Result<Option<Statvfs>> {
+	// still hanging since an earlier check
+	let statvfs = match tasks.stats.contains_key(&mount.mount_point) {
+		true => None,
+		false => {
+			let path = mount.mount_point.clone();
+			let mut stat = tokio::task::spawn_blocking(move || statvfs(&path));
+			match tokio::time::timeout(config.disk_stat_timeout, &mut stat).await {
+				Ok(statvfs) => Some(statvfs??),
+				// a hung statvfs can't be cancelled, so its thread is left behind until the call returns, if ever
+				Err(_) => {
+					tasks.stats.insert(mount.mount_point.clone(), stat);
+					None
+				}
+			}
+		}
+	};
+
+	let mount_point = mount.mount_point.to_string_lossy();
+	let evaluation = match statvfs {
+		Some(_) => Evaluation::Clear { threshold: None },
+		None => Evaluation::Firing {
+			tier: 0,
+			severity: config.disk_unresponsive_severity,
+			threshold: None,
+		},
+	};
+	let responsiveness = Observation {
+		check: format!("mount:{mount_point}"),
+		subject: mount_point.to_string(),
+		value: statvfs.is_none() as u64,
+		display: match statvfs {
+			Some(_) => "responsive".to_owned(),
+			None => format!("unresponsive ({} didn't answer within {}s)", mount.fs_type, config.disk_stat_timeout.as_secs()),
+		},
+		evaluation,
+		renotify: None,
+		context: None,
+	};
+	if let Err(e) = alerter.observe(responsiveness).await {
+		error!("Failed to alert on {mount_point} being unresponsive: {e}");
+	}
+	Ok(statvfs)
+}
+
+/// `tiers` are descending, so the last one `free` is below of is the most severe
+pub fn evaluate_free(free: InfoSize, tiers: &[FreeSpaceTier], reset: Option<InfoSize>) -> Evaluation {
+	let reset = reset.unwrap_or(tiers[0].below);
+	match tiers.iter().enumerate().rev().find(|(_, t)| free < t.below) {
+		Some((i, tier)) => Evaluation::Firing {
+			tier: i as u32,
+			severity: tier.severity,
+			threshold: Some(tier.below.to_string()),
+		},
+		None if free >= reset => Evaluation::Clear { threshold: Some(reset.to_string()) },
+		None => Evaluation::Hold,
+	}
+}
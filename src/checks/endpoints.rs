@@ -0,0 +1,117 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::eyre::Result;
+use futures::future::join_all;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error};
+
+use crate::{
+	alerts::{Alerter, Evaluation, Observation},
+	config::{Endpoint, MonitorConfig},
+	probe::{self, Probe},
+};
+
+/// Probes every endpoint each `endpoints_interval`, forever, alerting when one is down or slow
+pub async fn watch_endpoints(config: MonitorConfig, alerter: Arc<Alerter>) {
+	let endpoints: Vec<(&Endpoint, Probe)> = config
+		.endpoints
+		.iter()
+		.filter_map(|endpoint| match endpoint.probe() {
+			Ok(probe) => Some((endpoint, probe)),
+			Err(e) => {
+				error!("Not probing endpoint `{}`: {e}", endpoint.name);
+				None
+			}
+		})
+		.collect();
+	let client = match probe::client() {
+		Ok(client) => client,
+		Err(e) => {
+			error!("Can't set up an HTTP client, endpoints won't be probed: {e}");
+			return;
+		}
+	};
+	// consecutive failed probes of each endpoint
+	let mut failed = vec![0; endpoints.len()];
+
+	let mut tick = tokio::time::interval(config.endpoints_interval);
+	tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	loop {
+		tick.tick().await;
+		// all at once, so a few timing out don't hold back the rest
+		let results = join_all(endpoints.iter().map(|(endpoint, probe)| probe.run(&client, endpoint.timeout))).await;
+		for (((endpoint, _), result), failed) in endpoints.iter().zip(results).zip(&mut failed) {
+			*failed = match result {
+				Ok(_) => 0,
+				Err(_) => *failed + 1,
+			};
+			check_endpoint(&alerter, endpoint, result, *failed).await;
+		}
+	}
+}
+
+/// Alerts on the outcome of one probe of `endpoint`, `failed` being how many probes in a row have failed as of it
+pub async fn check_endpoint(alerter: &Alerter, endpoint: &Endpoint, result: Result<Duration>, failed: u32) {
+	let ms = |d: Duration| format!("{}ms", d.as_millis());
+	let availability = match &result {
+		Ok(latency) => {
+			debug!("Endpoint `{}` is up, took {}", endpoint.name, ms(*latency));
+			Observation {
+				check: format!("endpoint:{}", endpoint.name),
+				subject: endpoint.name.clone(),
+				value: 0,
+				display: "up".to_owned(),
+				evaluation: Evaluation::Clear { threshold: None },
+				renotify: None,
+				context: None,
+			}
+		}
+		Err(e) => {
+			debug!("Endpoint `{}` failed {failed} probes in a row: {e}", endpoint.name);
+			Observation {
+				check: format!("endpoint:{}", endpoint.name),
+				subject: endpoint.name.clone(),
+				value: 1,
+				display: "down".to_owned(),
+				// not yet down for long enough to tell a blip from an outage
+				evaluation: match failed >= endpoint.failures {
+					true => Evaluation::Firing {
+						tier: 0,
+						severity: endpoint.severity,
+						threshold: None,
+					},
+					false => Evaluation::Hold,
+				},
+				renotify: None,
+				context: Some(format!("{}: {e}", endpoint.url)),
+			}
+		}
+	};
+	if let Err(e) = alerter.observe(availability).await {
+		error!("Failed to alert on endpoint `{}`: {e}", endpoint.name);
+	}
+
+	// a down endpoint has no latency to speak of, so that alert stays as it was until it's back
+	if let (Some(max_latency), Ok(latency)) = (endpoint.max_latency, result) {
+		let evaluation = match latency > max_latency {
+			true => Evaluation::Firing {
+				tier: 0,
+				severity: endpoint.latency_severity,
+				threshold: Some(ms(max_latency)),
+			},
+			false => Evaluation::Clear { threshold: Some(ms(max_latency)) },
+		};
+		let observation = Observation {
+			check: format!("endpoint_latency:{}", endpoint.name),
+			subject: format!("{} latency", endpoint.name),
+			value: latency.as_millis() as u64,
+			display: ms(latency),
+			evaluation,
+			renotify: None,
+			context: None,
+		};
+		if let Err(e) = alerter.observe(observation).await {
+			error!("Failed to alert on endpoint `{}` latency: {e}", endpoint.name);
+		}
+	}
+}
@@ -0,0 +1,215 @@
+use std::{
+	collections::HashMap,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use tracing::{error, info};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{
+	alerts::Alerter,
+	config::MonitorConfig,
+	kernel_log::{KernelEvent, follow_kernel_log},
+	notify::Severity,
+};
+
+/// Alerts on OOM kills, hung tasks and filesystem errors as they show up in the kernel log, forever
+pub async fn watch_kernel_log(config: MonitorConfig, alerter: Arc<Alerter>) {
+	let mut messages = match follow_kernel_log().await {
+		Ok(messages) => messages,
+		Err(e) => {
+			error!("Can't follow the kernel log, OOM kills and kernel errors won't be alerted on: {e}");
+			return;
+		}
+	};
+
+	let mut cooldowns = Cooldowns::new(config.kernel_log_cooldown);
+	loop {
+		let flush_at = cooldowns.next_flush();
+		tokio::select! {
+			message = messages.recv() => {
+				let Some(message) = message else { break };
+				let Some(event) = KernelEvent::parse(&message) else {
+					continue;
+				};
+				let (check, value, mut text) = describe(&event);
+				let now = Instant::now();
+				match cooldowns.admit(check, subject(&event), now) {
+					Admission::HeldBack => {
+						info!("Holding back `{check}` alert, one was sent recently: {text}");
+						continue;
+					}
+					Admission::Send { held_back: Some(summary) } => text = format!("{text}\n(and {summary})"),
+					Admission::Send { held_back: None } => {}
+				}
+				match alerter.event(check, severity_of(&config, check), &value, text).await {
+					Ok(()) => cooldowns.alerted(check, now),
+					Err(e) => error!("Failed to alert on `{check}`: {e}"),
+				}
+			}
+			// so that what was held back doesn't wait for the next event, which may never come
+			_ = tokio::time::sleep_until(flush_at.unwrap_or_else(Instant::now).into()), if flush_at.is_some() => {
+				let now = Instant::now();
+				for (check, summary) in cooldowns.due(now) {
+					match alerter.event(check, severity_of(&config, check), "", format!("{}: {summary}", kind(check))).await {
+						Ok(()) => cooldowns.alerted(check, now),
+						Err(e) => {
+							error!("Failed to alert on held back `{check}` events: {e}");
+							cooldowns.retry_later(check, now);
+						}
+					}
+				}
+			}
+		}
+	}
+	error!("Kernel log stream ended, OOM kills and kernel errors are no longer alerted on");
+}
+
+/// How many held back events are named in a summary
+const MAX_NAMED: usize = 10;
+
+/// Kernel log alerts of each check go out at most once per period. Events in between are held back, and summarized in the next alert or once the period is over.
+#[derive(Debug)]
+pub struct Cooldowns {
+	period: Duration,
+	checks: HashMap<&'static str, Cooldown>,
+}
+
+#[derive(Debug)]
+struct Cooldown {
+	last_alert: Instant,
+	/// Subjects of the events held back since
+	held_back: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Admission {
+	HeldBack,
+	/// With a summary of the events held back before it, if any
+	Send {
+		held_back: Option<String>,
+	},
+}
+
+impl Cooldowns {
+	pub fn new(period: Duration) -> Self {
+		Self { period, checks: HashMap::new() }
+	}
+
+	/// Whether an event of `check` about `subject` may be alerted on now; if not, it's held back
+	pub fn admit(&mut self, check: &'static str, subject: String, now: Instant) -> Admission {
+		match self.checks.get_mut(check) {
+			Some(cooldown) if now.duration_since(cooldown.last_alert) < self.period => {
+				cooldown.held_back.push(subject);
+				Admission::HeldBack
+			}
+			Some(cooldown) => Admission::Send {
+				held_back: summary(&cooldown.held_back),
+			},
+			None => Admission::Send { held_back: None },
+		}
+	}
+
+	/// Starts the period over once an alert on `check` went out, which covered whatever was held back
+	pub fn alerted(&mut self, check: &'static str, now: Instant) {
+		self.checks.insert(
+			check,
+			Cooldown {
+				last_alert: now,
+				held_back: Vec::new(),
+			},
+		);
+	}
+
+	/// Postpones sending what was held back of `check` by another period
+	pub fn retry_later(&mut self, check: &'static str, now: Instant) {
+		if let Some(cooldown) = self.checks.get_mut(check) {
+			cooldown.last_alert = now;
+		}
+	}
+
+	/// When the earliest period with events held back is over
+	pub fn next_flush(&self) -> Option<Instant> {
+		self.checks.values().filter(|c| !c.held_back.is_empty()).map(|c| c.last_alert + self.period).min()
+	}
+
+	/// Summaries of the checks whose period is over with events held back, which are due to be sent on their own
+	pub fn due(&self, now: Instant) -> Vec<(&'static str, String)> {
+		self.checks
+			.iter()
+			.filter(|(_, c)| now.duration_since(c.last_alert) >= self.period)
+			.filter_map(|(&check, c)| Some((check, summary(&c.held_back)?)))
+			.collect()
+	}
+}
+
+/// e.g. "3 more since the last alert: java (pid 12), node (pid 34), java (pid 56)"
+fn summary(held_back: &[String]) -> Option<String> {
+	if held_back.is_empty() {
+		return None;
+	}
+	let mut names = held_back.iter().take(MAX_NAMED).cloned().collect::<Vec<_>>().join(", ");
+	if held_back.len() > MAX_NAMED {
+		names = format!("{names} and {} others", held_back.len() - MAX_NAMED);
+	}
+	Some(format!("{} more since the last alert: {names}", held_back.len()))
+}
+
+/// What a held back `event` is named by in summaries
+fn subject(event: &KernelEvent) -> String {
+	match event {
+		KernelEvent::OomKill { pid, process, .. } | KernelEvent::HungTask { pid, process, .. } => format!("{process} (pid {pid})"),
+		KernelEvent::FsError(message) => format!("`{message}`"),
+	}
+}
+
+fn kind(check: &str) -> &'static str {
+	match check {
+		"kernel:oom" => "OOM kills",
+		"kernel:hung_task" => "Hung tasks",
+		_ => "Filesystem errors",
+	}
+}
+
+fn severity_of(config: &MonitorConfig, check: &str) -> Severity {
+	match check {
+		"kernel:oom" => config.oom_severity,
+		"kernel:hung_task" => config.hung_task_severity,
+		_ => config.fs_error_severity,
+	}
+}
+
+/// Check ID, value and message of the alert for `event`
+fn describe(event: &KernelEvent) -> (&'static str, String, String) {
+	let bytes = |b: u64| InfoSize::from_parts(b, InfoSizeUnit::Byte);
+	match event {
+		KernelEvent::OomKill {
+			pid,
+			process,
+			anon_rss,
+			file_rss,
+			shmem_rss,
+			cgroup,
+		} => {
+			let rss = bytes(anon_rss + file_rss + shmem_rss);
+			let scope = match cgroup {
+				true => "its memory cgroup's limit",
+				false => "the system running out of memory",
+			};
+			let text = format!(
+				"OOM killer killed {process} (pid {pid}) over {scope}; it was using {rss} (anon {}, file {}, shmem {})",
+				bytes(*anon_rss),
+				bytes(*file_rss),
+				bytes(*shmem_rss)
+			);
+			("kernel:oom", rss.to_string(), text)
+		}
+		KernelEvent::HungTask { pid, process, secs } => (
+			"kernel:hung_task",
+			format!("{secs}s"),
+			format!("{process} (pid {pid}) has been blocked in the kernel for over {secs}s"),
+		),
+		KernelEvent::FsError(message) => ("kernel:fs_error", String::new(), format!("Filesystem or disk error: {message}")),
+	}
+}
@@ -0,0 +1,164 @@
+use std::{
+	collections::VecDeque,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error};
+
+use crate::{
+	alerts::{Alerter, Evaluation, Observation, format_duration},
+	checks::{evaluate_pct, memory::check_memory},
+	config::{LoadTier, MonitorConfig},
+	proc::{online_cpus, read_loadavg, read_pressure},
+};
+
+/// Samples memory, load and CPU/IO pressure every `sample_interval`, forever. Load and CPU/IO pressure alert once a tier has been held for all of `sustain`; memory, which doesn't come and go like they do, right away.
+pub async fn watch_load(config: MonitorConfig, alerter: Arc<Alerter>) {
+	let mut load = Window::new(config.sustain);
+	let mut cpu_pressure = Window::new(config.sustain);
+	let mut io_pressure = Window::new(config.sustain);
+
+	let mut tick = tokio::time::interval(config.sample_interval);
+	tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	loop {
+		tick.tick().await;
+		let now = Instant::now();
+
+		if let Err(e) = check_memory(&config, &alerter).await {
+			error!("Failed to check memory: {e}");
+		}
+
+		match read_loadavg() {
+			Ok(loadavg) => {
+				let cpus = online_cpus();
+				let per_cpu = loadavg.one / f64::from(cpus);
+				debug!("Load: {:.2} on {cpus} CPUs", loadavg.one);
+				load.push(now, per_cpu);
+				let observation = Observation {
+					check: "load".to_owned(),
+					subject: "load per CPU".to_owned(),
+					value: (per_cpu * 100.0) as u64,
+					display: format!("{per_cpu:.2}x"),
+					evaluation: load.sustained(now, |v| evaluate_load(v, &config.load_thresholds, config.load_reset)),
+					renotify: None,
+					context: Some(format!(
+						"Load average {:.2} / {:.2} / {:.2} over 1 / 5 / 15 min on {cpus} CPUs, held for {}",
+						loadavg.one,
+						loadavg.five,
+						loadavg.fifteen,
+						format_duration(config.sustain)
+					)),
+				};
+				if let Err(e) = alerter.observe(observation).await {
+					error!("Failed to alert on load: {e}");
+				}
+			}
+			Err(e) => error!("Failed to read load average: {e}"),
+		}
+
+		for (resource, window) in [("cpu", &mut cpu_pressure), ("io", &mut io_pressure)] {
+			if let Err(e) = sample_pressure(&config, &alerter, resource, window, now).await {
+				error!("Failed to alert on {resource} pressure: {e}");
+			}
+		}
+	}
+}
+
+/// `resource` is "cpu" or "io"
+async fn sample_pressure(config: &MonitorConfig, alerter: &Alerter, resource: &str, window: &mut Window, now: Instant) -> Result<()> {
+	let (thresholds, reset, severity) = match resource {
+		"cpu" => (&config.cpu_pressure_thresholds, config.cpu_pressure_reset, &config.cpu_pressure_severity),
+		_ => (&config.io_pressure_thresholds, config.io_pressure_reset, &config.io_pressure_severity),
+	};
+	let pressure = match read_pressure(resource) {
+		Ok(pressure) => pressure,
+		Err(e) => {
+			debug!("No {resource} pressure stall information (kernel built without PSI?): {e}");
+			return Ok(());
+		}
+	};
+	let stall = pressure.some.avg10;
+	debug!("{resource} pressure: tasks stalled {stall:.1}% of the last 10s");
+	window.push(now, stall);
+
+	alerter
+		.observe(Observation {
+			check: format!("{resource}_pressure"),
+			subject: format!("{resource} pressure"),
+			value: stall as u64,
+			display: format!("{stall:.1}%"),
+			evaluation: window.sustained(now, |v| evaluate_pct(v as u8, thresholds, reset, severity)),
+			renotify: None,
+			context: Some(format!(
+				"Some tasks stalled waiting on {resource} {:.1}% / {:.1}% / {:.1}% of the last 10s / 1m / 5m, held for {}",
+				pressure.some.avg10,
+				pressure.some.avg60,
+				pressure.some.avg300,
+				format_duration(config.sustain)
+			)),
+		})
+		.await
+}
+
+/// `tiers` are ascending, so the last one `per_cpu` is at or over is the most severe
+fn evaluate_load(per_cpu: f64, tiers: &[LoadTier], reset: f64) -> Evaluation {
+	match tiers.iter().enumerate().rev().find(|(_, t)| per_cpu >= t.above) {
+		Some((i, tier)) => Evaluation::Firing {
+			tier: i as u32,
+			severity: tier.severity,
+			threshold: Some(format!("{}x", tier.above)),
+		},
+		None if per_cpu < reset => Evaluation::Clear {
+			threshold: Some(format!("{reset}x")),
+		},
+		None => Evaluation::Hold,
+	}
+}
+
+/// Samples of one metric over the last `span`, for telling sustained breaches from spikes
+#[derive(Clone, Debug)]
+pub struct Window {
+	span: Duration,
+	/// When sampling began; the window isn't judged until it has been filled once
+	started: Option<Instant>,
+	samples: VecDeque<(Instant, f64)>,
+}
+
+impl Window {
+	pub fn new(span: Duration) -> Self {
+		Self {
+			span,
+			started: None,
+			samples: VecDeque::new(),
+		}
+	}
+
+	pub fn push(&mut self, at: Instant, value: f64) {
+		self.started.get_or_insert(at);
+		self.samples.push_back((at, value));
+		while self.samples.front().is_some_and(|(t, _)| at.duration_since(*t) > self.span) {
+			self.samples.pop_front();
+		}
+	}
+
+	/// Evaluates the level held throughout the window: fires on what even the lowest sample was over, and only clears once even the highest one is under.
+	/// Anything else, including a window not yet filled, holds the current state.
+	pub fn sustained(&self, now: Instant, evaluate: impl Fn(f64) -> Evaluation) -> Evaluation {
+		if self.started.is_none_or(|started| now.duration_since(started) < self.span) || self.samples.is_empty() {
+			return Evaluation::Hold;
+		}
+		let low = self.samples.iter().map(|(_, v)| *v).fold(f64::INFINITY, f64::min);
+		let high = self.samples.iter().map(|(_, v)| *v).fold(f64::NEG_INFINITY, f64::max);
+
+		match evaluate(low) {
+			firing @ Evaluation::Firing { .. } => firing,
+			_ => match evaluate(high) {
+				clear @ Evaluation::Clear { .. } => clear,
+				_ => Evaluation::Hold,
+			},
+		}
+	}
+}
@@ -0,0 +1,70 @@
+use color_eyre::eyre::Result;
+use tracing::{debug, error};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{
+	alerts::{Alerter, Observation},
+	checks::evaluate_pct,
+	config::MonitorConfig,
+	proc::{read_meminfo, read_pressure},
+};
+
+/// Checks memory and swap usage from /proc/meminfo, and how much time tasks spend stalled on memory from /proc/pressure/memory
+pub async fn check_memory(config: &MonitorConfig, alerter: &Alerter) -> Result<()> {
+	let meminfo = read_meminfo()?;
+	let bytes = |b: u64| InfoSize::from_parts(b, InfoSizeUnit::Byte);
+
+	let used_pct = meminfo.used_pct();
+	debug!("Memory usage: {used_pct}%, {} available of {}", bytes(meminfo.available), bytes(meminfo.total));
+	let memory = Observation {
+		check: "memory".to_owned(),
+		subject: "memory usage".to_owned(),
+		value: u64::from(used_pct),
+		display: format!("{used_pct}%"),
+		evaluation: evaluate_pct(used_pct, &config.memory_thresholds, config.memory_reset, &config.memory_severity),
+		renotify: None,
+		context: Some(format!("{} available of {}", bytes(meminfo.available), bytes(meminfo.total))),
+	};
+	if let Err(e) = alerter.observe(memory).await {
+		error!("Failed to alert on memory usage: {e}");
+	}
+
+	if let Some(swap_pct) = meminfo.swap_used_pct() {
+		debug!("Swap usage: {swap_pct}%");
+		let swap = Observation {
+			check: "swap".to_owned(),
+			subject: "swap usage".to_owned(),
+			value: u64::from(swap_pct),
+			display: format!("{swap_pct}%"),
+			evaluation: evaluate_pct(swap_pct, &config.swap_thresholds, config.swap_reset, &config.swap_severity),
+			renotify: None,
+			context: Some(format!("{} free of {}", bytes(meminfo.swap_free), bytes(meminfo.swap_total))),
+		};
+		if let Err(e) = alerter.observe(swap).await {
+			error!("Failed to alert on swap usage: {e}");
+		}
+	}
+
+	let pressure = match read_pressure("memory") {
+		Ok(pressure) => pressure,
+		Err(e) => {
+			debug!("No memory pressure stall information (kernel built without PSI?): {e}");
+			return Ok(());
+		}
+	};
+	let stall = pressure.some.avg60;
+	debug!("Memory pressure: tasks stalled {stall:.1}% of the last minute");
+	let observation = Observation {
+		check: "memory_pressure".to_owned(),
+		subject: "memory pressure".to_owned(),
+		value: stall as u64,
+		display: format!("{stall:.1}%"),
+		evaluation: evaluate_pct(stall as u8, &config.memory_pressure_thresholds, config.memory_pressure_reset, &config.memory_pressure_severity),
+		renotify: None,
+		context: Some(format!(
+			"Some tasks stalled waiting on memory {:.1}% / {:.1}% / {:.1}% of the last 10s / 1m / 5m",
+			pressure.some.avg10, pressure.some.avg60, pressure.some.avg300
+		)),
+	};
+	alerter.observe(observation).await
+}
@@ -0,0 +1,83 @@
+pub mod dirs;
+pub mod disk;
+pub mod endpoints;
+pub mod kernel;
+pub mod load;
+pub mod memory;
+pub mod processes;
+pub mod systemd;
+
+use std::time::Duration;
+
+use color_eyre::eyre::Result;
+
+use crate::{
+	alerts::{Alerter, Evaluation, Observation, format_duration},
+	config::{MonitorConfig, SeverityTiers},
+};
+
+/// Alerts when `eta`, the projected time until `subject` fills up, drops below the configured horizon; resolves once it's back over the reset
+pub async fn check_eta(config: &MonitorConfig, alerter: &Alerter, check: &str, subject: &str, eta: Option<Duration>) -> Result<()> {
+	let Some(horizon) = config.eta_horizon else {
+		return Ok(());
+	};
+	let reset = config.eta_reset.unwrap_or(horizon.mul_f64(1.5));
+
+	let evaluation = match eta {
+		Some(eta) if eta < horizon => Evaluation::Firing {
+			tier: 0,
+			severity: config.eta_severity,
+			threshold: Some(format_duration(horizon)),
+		},
+		// projections jitter with every sample, this keeps them from flapping around the horizon
+		Some(eta) if eta < reset => Evaluation::Hold,
+		_ => Evaluation::Clear {
+			threshold: Some(format_duration(reset)),
+		},
+	};
+
+	alerter
+		.observe(Observation {
+			check: format!("eta:{check}"),
+			subject: format!("time until {subject} is full"),
+			// the sooner, the worse
+			value: horizon.saturating_sub(eta.unwrap_or(horizon)).as_secs(),
+			display: eta_display(eta),
+			evaluation,
+			renotify: None,
+			context: None,
+		})
+		.await
+}
+
+/// Joins the non-empty parts into [Observation::context]
+pub fn join_context(parts: impl IntoIterator<Item = Option<String>>) -> Option<String> {
+	let parts: Vec<String> = parts.into_iter().flatten().filter(|p| !p.is_empty()).collect();
+	(!parts.is_empty()).then(|| parts.join("\n"))
+}
+
+/// Fires on the highest of the ascending `thresholds` that `pct` has reached, clears below `reset_threshold`
+pub fn evaluate_pct(usage_pct: u8, thresholds: &[u8], reset_threshold: u8, severity_tiers: &SeverityTiers) -> Evaluation {
+	// Find the highest threshold that current usage exceeds
+	let current_threshold = thresholds.iter().rev().find(|&&t| usage_pct >= t).copied();
+
+	match current_threshold {
+		Some(threshold) => Evaluation::Firing {
+			tier: u32::from(threshold),
+			severity: severity_tiers.severity(threshold),
+			threshold: Some(format!("{threshold}%")),
+		},
+		None if usage_pct < reset_threshold => Evaluation::Clear {
+			threshold: Some(format!("{reset_threshold}%")),
+		},
+		// usage_pct is between the reset threshold and the first tier, keep whatever state we're in
+		None => Evaluation::Hold,
+	}
+}
+
+pub fn eta_display(eta: Option<Duration>) -> String {
+	match eta {
+		Some(eta) => format!("~{}", format_duration(eta)),
+		None => "never".to_owned(),
+	}
+}
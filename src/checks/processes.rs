@@ -0,0 +1,208 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{
+	alerts::{Alerter, Evaluation, Observation},
+	config::{MonitorConfig, ProcessRule},
+	proc::{ProcessMatcher, ProcessStat, clock_ticks},
+};
+
+/// CPU time of every process seen on the last scan, for telling how busy it was since
+pub type CpuSamples = HashMap<u32, (Instant, u64)>;
+
+/// Scans /proc for the required processes every `processes_interval`, forever, alerting when one isn't running or goes over its ceilings
+pub async fn watch_processes(config: MonitorConfig, alerter: Arc<Alerter>) {
+	let (rules, matchers): (Vec<&ProcessRule>, Vec<ProcessMatcher>) = config
+		.processes
+		.iter()
+		.filter_map(|rule| match rule.matcher() {
+			Ok(matcher) => Some((rule, matcher)),
+			Err(e) => {
+				error!("Not checking process `{}`: {e}", rule.name);
+				None
+			}
+		})
+		.unzip();
+	let matchers = Arc::new(matchers);
+
+	let mut cpu = CpuSamples::new();
+	let mut tick = tokio::time::interval(config.processes_interval);
+	tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	loop {
+		tick.tick().await;
+		// one pass over /proc serves every rule
+		let matchers = Arc::clone(&matchers);
+		let found = match tokio::task::spawn_blocking(move || ProcessMatcher::find_all(&matchers)).await {
+			Ok(Ok(found)) => found,
+			Ok(Err(e)) => {
+				error!("Failed to scan for processes: {e}");
+				continue;
+			}
+			Err(e) => {
+				error!("Scanning for processes panicked: {e}");
+				continue;
+			}
+		};
+		let now = Instant::now();
+		let mut sampled = CpuSamples::new();
+		for (rule, processes) in rules.iter().zip(found) {
+			let processes = match processes {
+				Ok(processes) => processes,
+				Err(e) => {
+					error!("Failed to look for process `{}`: {e}", rule.name);
+					continue;
+				}
+			};
+			debug!("Process `{}`: {} running", rule.name, processes.len());
+			check_process(&alerter, rule, &processes, &cpu, now).await;
+			sampled.extend(processes.iter().map(|p| (p.pid, (now, p.cpu_ticks))));
+		}
+		cpu = sampled;
+	}
+}
+
+/// Observes whether `rule`'s process is running and, if it is, how its worst instance fares against the ceilings. `cpu` holds the CPU times of the previous scan.
+pub async fn check_process(alerter: &Alerter, rule: &ProcessRule, processes: &[ProcessStat], cpu: &CpuSamples, now: Instant) {
+	let (display, evaluation) = match processes {
+		[] => (
+			"not running".to_owned(),
+			Evaluation::Firing {
+				tier: 0,
+				severity: rule.severity,
+				threshold: None,
+			},
+		),
+		[process] => (format!("running (pid {})", process.pid), Evaluation::Clear { threshold: None }),
+		_ => (format!("running ({} processes)", processes.len()), Evaluation::Clear { threshold: None }),
+	};
+	let presence = Observation {
+		check: format!("process:{}", rule.name),
+		subject: rule.name.clone(),
+		value: processes.is_empty() as u64,
+		display,
+		evaluation,
+		renotify: None,
+		context: None,
+	};
+	if let Err(e) = alerter.observe(presence).await {
+		error!("Failed to alert on process `{}`: {e}", rule.name);
+	}
+	if processes.is_empty() {
+		clear_limits(alerter, rule).await;
+		return;
+	}
+
+	let bytes = |b: u64| InfoSize::from_parts(b, InfoSizeUnit::Byte);
+	if let Some(max_rss) = rule.max_rss
+		&& let Some(worst) = processes.iter().max_by_key(|p| p.rss)
+	{
+		let limit = Limit {
+			kind: "rss",
+			label: "RSS",
+			over: bytes(worst.rss) > max_rss,
+			value: worst.rss,
+			display: bytes(worst.rss).to_string(),
+			threshold: max_rss.to_string(),
+		};
+		check_limit(alerter, rule, limit, worst).await;
+	}
+
+	if let Some(max_cpu) = rule.max_cpu {
+		// % of one core since the last scan; processes seen for the first time have nothing to compare against yet
+		let usage = |p: &ProcessStat| {
+			let (at, ticks) = cpu.get(&p.pid)?;
+			let secs = now.duration_since(*at).as_secs_f64();
+			(secs > 0.0).then(|| p.cpu_ticks.saturating_sub(*ticks) as f64 / clock_ticks() as f64 / secs * 100.0)
+		};
+		if let Some((worst, pct)) = processes.iter().filter_map(|p| Some((p, usage(p)?))).max_by(|a, b| a.1.total_cmp(&b.1)) {
+			let limit = Limit {
+				kind: "cpu",
+				label: "CPU usage",
+				over: pct > max_cpu,
+				value: pct as u64,
+				display: format!("{pct:.0}%"),
+				threshold: format!("{max_cpu}%"),
+			};
+			check_limit(alerter, rule, limit, worst).await;
+		}
+	}
+
+	if let Some(max_fds) = rule.max_fds
+		&& let Some((worst, fds)) = processes.iter().filter_map(|p| Some((p, p.fds?))).max_by_key(|(_, fds)| *fds)
+	{
+		let limit = Limit {
+			kind: "fds",
+			label: "open files",
+			over: fds > max_fds,
+			value: fds,
+			display: fds.to_string(),
+			threshold: max_fds.to_string(),
+		};
+		check_limit(alerter, rule, limit, worst).await;
+	}
+}
+
+/// A ceiling of a rule, as measured on its worst process
+struct Limit {
+	/// Goes into the check ID
+	kind: &'static str,
+	label: &'static str,
+	over: bool,
+	value: u64,
+	display: String,
+	threshold: String,
+}
+
+/// A process that's gone is over none of its ceilings; without this, their alerts would stay on until it's back and under them
+async fn clear_limits(alerter: &Alerter, rule: &ProcessRule) {
+	let firing = alerter.firing("process_");
+	let limits = [
+		("rss", "RSS", rule.max_rss.map(|max| max.to_string())),
+		("cpu", "CPU usage", rule.max_cpu.map(|max| format!("{max}%"))),
+		("fds", "open files", rule.max_fds.map(|max| max.to_string())),
+	];
+	for (kind, label, threshold) in limits {
+		let check = format!("process_{kind}:{}", rule.name);
+		if !firing.contains(&check) {
+			continue;
+		}
+		let observation = Observation {
+			check,
+			subject: format!("{} {label}", rule.name),
+			value: 0,
+			display: "none, not running".to_owned(),
+			evaluation: Evaluation::Clear { threshold },
+			renotify: None,
+			context: None,
+		};
+		if let Err(e) = alerter.observe(observation).await {
+			error!("Failed to alert on process `{}` {label}: {e}", rule.name);
+		}
+	}
+}
+
+async fn check_limit(alerter: &Alerter, rule: &ProcessRule, limit: Limit, worst: &ProcessStat) {
+	let evaluation = match limit.over {
+		true => Evaluation::Firing {
+			tier: 0,
+			severity: rule.limit_severity,
+			threshold: Some(limit.threshold),
+		},
+		false => Evaluation::Clear { threshold: Some(limit.threshold) },
+	};
+	let observation = Observation {
+		check: format!("process_{}:{}", limit.kind, rule.name),
+		subject: format!("{} {}", rule.name, limit.label),
+		value: limit.value,
+		display: limit.display,
+		evaluation,
+		renotify: None,
+		context: Some(format!("pid {}: {}", worst.pid, worst.cmdline.join(" "))),
+	};
+	if let Err(e) = alerter.observe(observation).await {
+		error!("Failed to alert on process `{}` {}: {e}", rule.name, limit.label);
+	}
+}
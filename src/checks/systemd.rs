@@ -0,0 +1,194 @@
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	sync::Arc,
+	time::Instant,
+};
+
+use color_eyre::eyre::Result;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error};
+
+use crate::{
+	alerts::{Alerter, Evaluation, Observation, format_duration},
+	config::MonitorConfig,
+	systemd::{Systemd, Unit, journal_tail},
+};
+
+const FAILED_PREFIX: &str = "systemd:";
+const RESTARTS_PREFIX: &str = "systemd_restarts:";
+
+/// Per service: its restart counter as sampled over the last `systemd_restart_window`, plus the last sample from before it as the baseline. Oldest first.
+pub type RestartHistory = HashMap<String, VecDeque<(Instant, u32)>>;
+
+/// Checks systemd units every `systemd_interval`, forever, alerting on units that failed or keep restarting
+pub async fn watch_systemd(config: MonitorConfig, alerter: Arc<Alerter>) {
+	let systemd = match Systemd::system().await {
+		Ok(systemd) => systemd,
+		Err(e) => {
+			error!("Can't connect to systemd over D-Bus, failed units won't be alerted on: {e}");
+			return;
+		}
+	};
+
+	let mut restarts = RestartHistory::new();
+	let mut tick = tokio::time::interval(config.systemd_interval);
+	tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+	loop {
+		tick.tick().await;
+		if let Err(e) = check_units(&config, &alerter, &systemd, &mut restarts).await {
+			error!("Failed to check systemd units: {e}");
+		}
+	}
+}
+
+/// One round of [watch_systemd]; `restarts` carries over between rounds
+pub async fn check_units(config: &MonitorConfig, alerter: &Alerter, systemd: &Systemd, restarts: &mut RestartHistory) -> Result<()> {
+	let units = systemd.units().await?;
+	debug!("systemd has {} units loaded", units.len());
+	check_failed(config, alerter, &units).await;
+	check_restarts(config, alerter, systemd, &units, restarts).await;
+	Ok(())
+}
+
+/// Only units that failed, or were failed when last checked, are observed; the rest have nothing to report
+async fn check_failed(config: &MonitorConfig, alerter: &Alerter, units: &[Unit]) {
+	let failing = alerter.firing(FAILED_PREFIX);
+	for unit in units {
+		let check = format!("{FAILED_PREFIX}{}", unit.name);
+		let failed = unit.active_state == "failed";
+		if !failed && !failing.contains(&check) {
+			continue;
+		}
+		let evaluation = match failed {
+			true => Evaluation::Firing {
+				tier: 0,
+				severity: config.systemd_severity,
+				threshold: None,
+			},
+			false => Evaluation::Clear { threshold: None },
+		};
+		let observation = Observation {
+			check,
+			subject: unit.name.clone(),
+			value: failed as u64,
+			display: unit.active_state.clone(),
+			evaluation,
+			renotify: None,
+			context: None,
+		};
+		observe_with_journal(config, alerter, observation, &unit.name).await;
+	}
+
+	// failed transient units are gone altogether once reset
+	for check in failing {
+		let name = &check[FAILED_PREFIX.len()..];
+		if units.iter().any(|u| u.name == name) {
+			continue;
+		}
+		let observation = Observation {
+			check: check.clone(),
+			subject: name.to_owned(),
+			value: 0,
+			display: "unloaded".to_owned(),
+			evaluation: Evaluation::Clear { threshold: None },
+			renotify: None,
+			context: None,
+		};
+		if let Err(e) = alerter.observe(observation).await {
+			error!("Failed to alert on `{check}`: {e}");
+		}
+	}
+}
+
+/// A service is restart-looping when systemd restarted it `systemd_restart_threshold` times within `systemd_restart_window`
+async fn check_restarts(config: &MonitorConfig, alerter: &Alerter, systemd: &Systemd, units: &[Unit], restarts: &mut RestartHistory) {
+	let now = Instant::now();
+	let window = format_duration(config.systemd_restart_window);
+	let looping = alerter.firing(RESTARTS_PREFIX);
+	let mut sampled = HashSet::new();
+
+	for unit in units.iter().filter(|u| u.is_service()) {
+		let check = format!("{RESTARTS_PREFIX}{}", unit.name);
+		// every service, every round: one that crashes a while after starting is `active` most of the time, so its state is no hint it's looping
+		let count = match systemd.n_restarts(unit).await {
+			Ok(count) => count,
+			Err(e) => {
+				debug!("Can't read the restart count of {}: {e}", unit.name);
+				continue;
+			}
+		};
+		let history = restarts.entry(unit.name.clone()).or_default();
+		history.push_back((now, count));
+		while history.get(1).is_some_and(|(at, _)| now.duration_since(*at) >= config.systemd_restart_window) {
+			history.pop_front();
+		}
+		// the counter starts over when the service is started by hand
+		let baseline = history.front().map_or(count, |(_, c)| *c);
+		let recent = count.saturating_sub(baseline);
+		sampled.insert(unit.name.clone());
+		if recent == 0 && !looping.contains(&check) {
+			continue;
+		}
+
+		let evaluation = if recent >= config.systemd_restart_threshold {
+			Evaluation::Firing {
+				tier: 0,
+				severity: config.systemd_restart_severity,
+				threshold: Some(format!("{} in {window}", config.systemd_restart_threshold)),
+			}
+		} else if recent == 0 {
+			Evaluation::Clear {
+				threshold: Some(format!("0 in {window}")),
+			}
+		} else {
+			Evaluation::Hold
+		};
+		let observation = Observation {
+			check,
+			subject: format!("{} restarts", unit.name),
+			value: recent as u64,
+			display: format!("{recent} in {window}"),
+			evaluation,
+			renotify: None,
+			context: None,
+		};
+		observe_with_journal(config, alerter, observation, &unit.name).await;
+	}
+	restarts.retain(|name, _| sampled.contains(name));
+
+	for check in looping {
+		let name = &check[RESTARTS_PREFIX.len()..];
+		if units.iter().any(|u| u.name == name) {
+			continue;
+		}
+		let observation = Observation {
+			check: check.clone(),
+			subject: format!("{name} restarts"),
+			value: 0,
+			display: "none, unit unloaded".to_owned(),
+			evaluation: Evaluation::Clear {
+				threshold: Some(format!("0 in {window}")),
+			},
+			renotify: None,
+			context: None,
+		};
+		if let Err(e) = alerter.observe(observation).await {
+			error!("Failed to alert on `{check}`: {e}");
+		}
+	}
+}
+
+/// Observes, attaching the unit's last journal lines if that sends an alert
+async fn observe_with_journal(config: &MonitorConfig, alerter: &Alerter, mut observation: Observation, unit: &str) {
+	if config.systemd_journal_lines > 0 && alerter.will_fire(&observation) {
+		match journal_tail(unit, config.systemd_journal_lines).await {
+			Ok(lines) if !lines.is_empty() => observation.context = Some(format!("Last journal lines:\n{lines}")),
+			Ok(_) => {}
+			Err(e) => debug!("Can't read the journal of {unit}: {e}"),
+		}
+	}
+	let check = observation.check.clone();
+	if let Err(e) = alerter.observe(observation).await {
+		error!("Failed to alert on `{check}`: {e}");
+	}
+}
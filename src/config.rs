@@ -1,12 +1,161 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use color_eyre::eyre::{Result, bail, eyre};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use tracing::warn;
 use v_utils::{
 	macros::{MyConfigPrimitives, Settings},
 	utils::InfoSize,
 };
 
+use crate::{alerts::format_duration, notify::Severity, probe::Probe, proc::ProcessMatcher};
+
 #[derive(Clone, Debug, Default, MyConfigPrimitives, Settings)]
 pub struct AppConfig {
-	pub telegram: TelegramConfig,
+	#[serde(default)]
+	pub alerts: AlertsConfig,
 	pub monitor: MonitorConfig,
+	/// Applies to every filesystem walk: directory size checks, their indexes and tempfile cleanup
+	#[serde(default)]
+	pub throttle: ThrottleConfig,
+	/// Deprecated: the only way to be alerted before `alerts.sinks`; taken over as a telegram sink by [migrate_legacy](Self::migrate_legacy)
+	#[serde(default)]
+	pub telegram: Option<TelegramConfig>,
+}
+
+impl AppConfig {
+	/// Moves the deprecated top-level `telegram` section into `alerts.sinks`, and migrates the deprecated monitor settings, so configs from before them keep working
+	pub fn migrate_legacy(&mut self) -> Result<()> {
+		if let Some(telegram) = self.telegram.take() {
+			if self.alerts.sinks.iter().any(|sink| sink.name() == "telegram") {
+				bail!("telegram is deprecated and there's already a sink named `telegram` in `alerts.sinks`; remove the top-level `telegram` section");
+			}
+			warn!("telegram is deprecated; move it to `alerts.sinks` as a sink of `type = \"telegram\"` instead");
+			self.alerts.sinks.push(SinkConfig {
+				name: None,
+				kind: SinkKind::Telegram(telegram),
+			});
+		}
+		self.monitor.migrate_legacy()
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		self.alerts.validate()?;
+		self.monitor.validate()?;
+		if self.throttle.nice > 19 {
+			bail!("throttle.nice: {} is out of the 0-19 range", self.throttle.nice);
+		}
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug, Default, MyConfigPrimitives)]
+pub struct ThrottleConfig {
+	/// Only do disk IO when no one else needs the disk (the idle ioprio class, as `ionice -c3`)
+	#[serde(default)]
+	pub io_idle: bool,
+	/// CPU niceness, 0-19
+	#[serde(default)]
+	pub nice: u8,
+	/// Cap on how many directory entries a walk looks at per second
+	#[serde(default)]
+	pub entries_per_sec: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, MyConfigPrimitives)]
+pub struct AlertsConfig {
+	/// Where alerts can be delivered. Without `routes`, every alert goes to each of these
+	#[serde(default)]
+	pub sinks: Vec<SinkConfig>,
+	/// Tags of this host, for matching `routes` (e.g. "prod", "db")
+	#[serde(default)]
+	pub host_tags: Vec<String>,
+	/// Evaluated in order; the first matching route decides which sinks an alert goes to.
+	/// One of them has to be a catch-all without any conditions, so that no alert goes undelivered.
+	#[serde(default)]
+	pub routes: Vec<RouteConfig>,
+}
+
+impl AlertsConfig {
+	pub fn validate(&self) -> Result<()> {
+		if self.sinks.is_empty() {
+			bail!("alerts.sinks: none configured, so alerts would go nowhere");
+		}
+		for (i, sink) in self.sinks.iter().enumerate() {
+			if self.sinks[..i].iter().any(|s| s.name() == sink.name()) {
+				bail!("alerts.sinks: more than one sink is named `{}`; give them distinct `name`s", sink.name());
+			}
+		}
+		if !self.routes.is_empty() && !self.routes.iter().any(RouteConfig::is_catch_all) {
+			bail!("alerts.routes: needs a catch-all route without any conditions, for alerts no other route matches");
+		}
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct SinkConfig {
+	/// For referring to the sink from `routes`; defaults to its type (e.g. "telegram")
+	#[serde(default)]
+	pub name: Option<String>,
+	#[serde(flatten)]
+	pub kind: SinkKind,
+}
+
+impl SinkConfig {
+	pub fn name(&self) -> &str {
+		self.name.as_deref().unwrap_or_else(|| self.kind.kind())
+	}
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkKind {
+	Telegram(TelegramConfig),
+	Email(EmailConfig),
+	Webhook(WebhookConfig),
+	Matrix(MatrixConfig),
+	Ntfy(NtfyConfig),
+	Gotify(GotifyConfig),
+}
+
+impl SinkKind {
+	pub fn kind(&self) -> &'static str {
+		match self {
+			Self::Telegram(_) => "telegram",
+			Self::Email(_) => "email",
+			Self::Webhook(_) => "webhook",
+			Self::Matrix(_) => "matrix",
+			Self::Ntfy(_) => "ntfy",
+			Self::Gotify(_) => "gotify",
+		}
+	}
+}
+
+/// All conditions present must hold for the route to match
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct RouteConfig {
+	/// Check IDs; a trailing `*` matches any suffix (e.g. "disk:*")
+	#[serde(default)]
+	pub checks: Vec<String>,
+	#[serde(default)]
+	pub min_severity: Option<Severity>,
+	/// Each of these must be among `host_tags`
+	#[serde(default)]
+	pub host_tags: Vec<String>,
+	/// Local time window, "HH:MM-HH:MM"; may wrap around midnight (e.g. "22:00-08:00")
+	#[serde(default)]
+	pub between: Option<String>,
+	/// Names of the sinks to deliver to
+	pub sinks: Vec<String>,
+}
+
+impl RouteConfig {
+	/// Matches every alert
+	pub fn is_catch_all(&self) -> bool {
+		self.checks.is_empty() && self.min_severity.is_none() && self.host_tags.is_empty() && self.between.is_none()
+	}
 }
 
 #[derive(Clone, Debug, Default, MyConfigPrimitives)]
@@ -15,19 +164,922 @@ pub struct TelegramConfig {
 	pub alerts_chat: String,
 }
 
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct EmailConfig {
+	/// SMTP server to relay through
+	pub host: String,
+	/// Defaults to the standard port of the chosen `tls` mode (587, 465 or 25)
+	#[serde(default)]
+	pub port: Option<u16>,
+	#[serde(default)]
+	pub tls: SmtpTls,
+	#[serde(default)]
+	pub username: Option<String>,
+	#[serde(default)]
+	pub password: Option<String>,
+	pub from: String,
+	pub to: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+	#[default]
+	Starttls,
+	Implicit,
+	/// Plaintext; only for local relays
+	None,
+}
+
+/// Arbitrary HTTP endpoint (incident service, Slack/Discord incoming webhooks, ...)
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct WebhookConfig {
+	pub url: String,
+	#[serde(default = "__default_webhook_method")]
+	pub method: String,
+	#[serde(default)]
+	pub headers: BTreeMap<String, String>,
+	/// JSON body; `{{host}}`, `{{check}}`, `{{severity}}`, `{{status}}` ("firing" or "resolved"), `{{value}}`, `{{threshold}}` and `{{message}}` are substituted
+	#[serde(default = "__default_webhook_body")]
+	pub body: String,
+}
+
+fn __default_webhook_method() -> String {
+	"POST".to_owned()
+}
+
+fn __default_webhook_body() -> String {
+	r#"{"text": "{{message}}"}"#.to_owned()
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct MatrixConfig {
+	/// Base URL of the homeserver (e.g. "https://matrix.org")
+	pub homeserver: String,
+	pub access_token: String,
+	/// Room ID (e.g. "!abcdef:matrix.org"); the bot must already be joined
+	pub room_id: String,
+	/// Also send an HTML-formatted body
+	#[serde(default = "__default_true")]
+	pub html: bool,
+}
+
+fn __default_true() -> bool {
+	true
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct NtfyConfig {
+	/// e.g. "https://ntfy.sh"
+	pub server: String,
+	pub topic: String,
+	/// Access token, for protected topics
+	#[serde(default)]
+	pub token: Option<String>,
+	/// Overrides of the default severity -> priority (1..=5) mapping
+	#[serde(default)]
+	pub priorities: BTreeMap<Severity, u8>,
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct GotifyConfig {
+	pub server: String,
+	/// Token of the Gotify application to post as
+	pub app_token: String,
+	/// Overrides of the default severity -> priority (0..=10) mapping
+	#[serde(default)]
+	pub priorities: BTreeMap<Severity, u8>,
+}
+
 #[derive(Clone, Debug, MyConfigPrimitives)]
 pub struct MonitorConfig {
-	/// Maximum allowed size for ~/.local/state (e.g., "20GB", "500MB")
-	#[serde(default = "__default_max_size")]
+	/// How often to run the checks
+	#[serde(default = "__default_interval", with = "humantime_serde")]
+	pub interval: Duration,
+	/// Directories to keep under a size budget; just the state directory ($XDG_STATE_HOME, ~/.local/state) at 10GB by default, under the check ID `state_dir`
+	#[serde(default = "__default_watched_dirs")]
+	pub watched_dirs: Vec<WatchedDir>,
+	/// How many of the largest directories and files to list in size alerts; 0 to disable. For mounts, they're sent as a follow-up once the filesystem is walked.
+	#[serde(default = "__default_top_consumers")]
+	pub top_consumers: usize,
+	/// Threads in the pool all directory walks share; 0 for one per core
+	#[serde(default)]
+	pub walk_threads: usize,
+	/// How often `incremental` watched directories are fully rescanned, to correct for changes the index missed
+	#[serde(default = "__default_reconcile_interval", with = "humantime_serde")]
+	pub reconcile_interval: Duration,
+	/// Disk usage tiers in %, ascending; crossing each one alerts
+	#[serde(default = "__default_disk_thresholds")]
+	pub disk_thresholds: Vec<u8>,
+	/// Disk usage % to drop below for the alert to resolve; must be below the first tier
+	#[serde(default = "__default_disk_reset")]
+	pub disk_reset: u8,
+	#[serde(default)]
+	pub disk_severity: SeverityTiers,
+	/// Free space tiers, descending; dropping below each one alerts. None by default.
+	#[serde(default)]
+	pub disk_min_free: Vec<FreeSpaceTier>,
+	/// Free space to get back above for the alert to resolve; defaults to the first `disk_min_free` tier
+	#[serde(default)]
+	pub disk_min_free_reset: Option<InfoSize>,
+	#[serde(default = "__default_inode_thresholds")]
+	pub inode_thresholds: Vec<u8>,
+	#[serde(default = "__default_inode_reset")]
+	pub inode_reset: u8,
+	#[serde(default = "__default_inode_severity")]
+	pub inode_severity: SeverityTiers,
+	/// Memory usage tiers in %, counting page cache that can be dropped as free (i.e. MemAvailable)
+	#[serde(default = "__default_memory_thresholds")]
+	pub memory_thresholds: Vec<u8>,
+	#[serde(default = "__default_memory_reset")]
+	pub memory_reset: u8,
+	#[serde(default)]
+	pub memory_severity: SeverityTiers,
+	/// Swap usage tiers in %; not checked on hosts without swap
+	#[serde(default = "__default_swap_thresholds")]
+	pub swap_thresholds: Vec<u8>,
+	#[serde(default = "__default_swap_reset")]
+	pub swap_reset: u8,
+	#[serde(default = "__default_swap_severity")]
+	pub swap_severity: SeverityTiers,
+	/// Tiers of the share of the last minute (in %) some tasks spent stalled waiting on memory, from /proc/pressure/memory
+	#[serde(default = "__default_memory_pressure_thresholds")]
+	pub memory_pressure_thresholds: Vec<u8>,
+	#[serde(default = "__default_memory_pressure_reset")]
+	pub memory_pressure_reset: u8,
+	#[serde(default = "__default_memory_pressure_severity")]
+	pub memory_pressure_severity: SeverityTiers,
+	/// How often memory, load and CPU/IO pressure are sampled; they're checked apart from the rest, to catch what happens between `interval`s
+	#[serde(default = "__default_sample_interval", with = "humantime_serde")]
+	pub sample_interval: Duration,
+	/// How long load or CPU/IO pressure has to stay over a tier before it alerts, and back under the reset before it resolves
+	#[serde(default = "__default_sustain", with = "humantime_serde")]
+	pub sustain: Duration,
+	/// 1-minute load average tiers, as multiples of the number of online CPUs; ascending
+	#[serde(default = "__default_load_thresholds")]
+	pub load_thresholds: Vec<LoadTier>,
+	#[serde(default = "__default_load_reset")]
+	pub load_reset: f64,
+	/// Tiers of the share of time (in %) some tasks spent waiting for a CPU, from /proc/pressure/cpu
+	#[serde(default = "__default_cpu_pressure_thresholds")]
+	pub cpu_pressure_thresholds: Vec<u8>,
+	#[serde(default = "__default_cpu_pressure_reset")]
+	pub cpu_pressure_reset: u8,
+	#[serde(default = "__default_cpu_pressure_severity")]
+	pub cpu_pressure_severity: SeverityTiers,
+	/// Same as `cpu_pressure_*`, for tasks waiting on IO, from /proc/pressure/io
+	#[serde(default = "__default_io_pressure_thresholds")]
+	pub io_pressure_thresholds: Vec<u8>,
+	#[serde(default = "__default_io_pressure_reset")]
+	pub io_pressure_reset: u8,
+	#[serde(default = "__default_io_pressure_severity")]
+	pub io_pressure_severity: SeverityTiers,
+	/// Alert on OOM kills, hung tasks and filesystem errors showing up in the kernel log
+	#[serde(default = "__default_true")]
+	pub kernel_log: bool,
+	/// Kernel log alerts of the same kind are sent at most this often; the ones in between are summarized, naming each process, once it is over
+	#[serde(default = "__default_kernel_log_cooldown", with = "humantime_serde")]
+	pub kernel_log_cooldown: Duration,
+	#[serde(default = "__default_critical_severity")]
+	pub oom_severity: Severity,
+	#[serde(default)]
+	pub hung_task_severity: Severity,
+	#[serde(default = "__default_critical_severity")]
+	pub fs_error_severity: Severity,
+	/// Alert on systemd units that failed or are restart-looping, asking systemd over D-Bus
+	#[serde(default = "__default_true")]
+	pub systemd: bool,
+	/// How often units are checked
+	#[serde(default = "__default_check_interval", with = "humantime_serde")]
+	pub systemd_interval: Duration,
+	#[serde(default = "__default_critical_severity")]
+	pub systemd_severity: Severity,
+	/// A service restarted this many times within `systemd_restart_window` is restart-looping
+	#[serde(default = "__default_systemd_restart_threshold")]
+	pub systemd_restart_threshold: u32,
+	#[serde(default = "__default_systemd_restart_window", with = "humantime_serde")]
+	pub systemd_restart_window: Duration,
+	#[serde(default)]
+	pub systemd_restart_severity: Severity,
+	/// How many of the unit's last journal lines to attach to its alerts
+	#[serde(default = "__default_systemd_journal_lines")]
+	pub systemd_journal_lines: usize,
+	/// Processes that must be running, for daemons not managed by systemd
+	#[serde(default)]
+	pub processes: Vec<ProcessRule>,
+	/// How often /proc is scanned for them; also the span their CPU usage is averaged over
+	#[serde(default = "__default_check_interval", with = "humantime_serde")]
+	pub processes_interval: Duration,
+	/// Network endpoints to probe, mainly local services' health checks
+	#[serde(default)]
+	pub endpoints: Vec<Endpoint>,
+	#[serde(default = "__default_check_interval", with = "humantime_serde")]
+	pub endpoints_interval: Duration,
+	/// Alert when a mount or a watched directory is projected to fill its filesystem within this (e.g. "24h"); off by default
+	#[serde(default, with = "humantime_serde")]
+	pub eta_horizon: Option<Duration>,
+	/// The alert resolves once the projection is back over this; defaults to 1.5 × `eta_horizon`
+	#[serde(default, with = "humantime_serde")]
+	pub eta_reset: Option<Duration>,
+	/// How far back usage samples are used for fitting the growth rate
+	#[serde(default = "__default_eta_window", with = "humantime_serde")]
+	pub eta_window: Duration,
+	#[serde(default = "__default_critical_severity")]
+	pub eta_severity: Severity,
+	/// Also check filesystems of the built-in list of pseudo filesystem types ([PSEUDO_FS_TYPES](crate::mounts::PSEUDO_FS_TYPES))
+	#[serde(default)]
+	pub disk_include_pseudo: bool,
+	/// Filesystem types to skip on top of the pseudo and network ones (e.g. "vfat")
+	#[serde(default)]
+	pub disk_ignore_fs_types: Vec<String>,
+	/// Per-mount overrides, keyed by mount point
+	#[serde(default)]
+	pub disk_mounts: BTreeMap<String, MountConfig>,
+	/// A mount not answering how full it is within this (e.g. a network filesystem whose server went away) is alerted on as unresponsive
+	#[serde(default = "__default_disk_stat_timeout", with = "humantime_serde")]
+	pub disk_stat_timeout: Duration,
+	#[serde(default = "__default_critical_severity")]
+	pub disk_unresponsive_severity: Severity,
+	/// Deprecated: `max_size*` configured the state directory's budget before `watched_dirs`, and are moved onto its entry there by [Self::migrate_legacy]
+	#[serde(default)]
+	pub max_size: Option<InfoSize>,
+	#[serde(default)]
+	pub max_size_severity: Option<Severity>,
+	#[serde(default)]
+	pub max_size_reset: Option<InfoSize>,
+	#[serde(default, with = "humantime_serde")]
+	pub max_size_renotify: Option<Duration>,
+}
+
+impl MonitorConfig {
+	/// Applies the deprecated `max_size*` settings to the `state_dir` entry of `watched_dirs`, so configs from before it keep working
+	pub fn migrate_legacy(&mut self) -> Result<()> {
+		if self.max_size.is_none() && self.max_size_severity.is_none() && self.max_size_reset.is_none() && self.max_size_renotify.is_none() {
+			return Ok(());
+		}
+		let Some(dir) = self.watched_dirs.iter_mut().find(|d| d.id.as_deref() == Some(STATE_DIR_CHECK)) else {
+			bail!("max_size, max_size_severity, max_size_reset and max_size_renotify are deprecated; set them on the directory's entry in `watched_dirs` instead");
+		};
+		warn!("max_size, max_size_severity, max_size_reset and max_size_renotify are deprecated; set them on the state directory's entry in `watched_dirs` instead");
+		if let Some(max_size) = self.max_size.take() {
+			dir.max_size = max_size;
+		}
+		if let Some(severity) = self.max_size_severity.take() {
+			dir.severity = severity;
+		}
+		dir.reset = self.max_size_reset.take().or(dir.reset);
+		dir.renotify = self.max_size_renotify.take().or(dir.renotify);
+		Ok(())
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.interval.is_zero() {
+			bail!("interval must be positive");
+		}
+		validate_tiers("disk_thresholds", &self.disk_thresholds, self.disk_reset)?;
+		validate_tiers("inode_thresholds", &self.inode_thresholds, self.inode_reset)?;
+		validate_free_tiers("disk_min_free", &self.disk_min_free, self.disk_min_free_reset)?;
+		validate_tiers("memory_thresholds", &self.memory_thresholds, self.memory_reset)?;
+		validate_tiers("swap_thresholds", &self.swap_thresholds, self.swap_reset)?;
+		validate_tiers("memory_pressure_thresholds", &self.memory_pressure_thresholds, self.memory_pressure_reset)?;
+		validate_tiers("cpu_pressure_thresholds", &self.cpu_pressure_thresholds, self.cpu_pressure_reset)?;
+		validate_tiers("io_pressure_thresholds", &self.io_pressure_thresholds, self.io_pressure_reset)?;
+		for (name, tiers) in [
+			("disk_severity", &self.disk_severity),
+			("inode_severity", &self.inode_severity),
+			("memory_severity", &self.memory_severity),
+			("swap_severity", &self.swap_severity),
+			("memory_pressure_severity", &self.memory_pressure_severity),
+			("cpu_pressure_severity", &self.cpu_pressure_severity),
+			("io_pressure_severity", &self.io_pressure_severity),
+		] {
+			tiers.validate(name)?;
+		}
+		if !self.load_thresholds.is_sorted_by(|a, b| a.above < b.above) {
+			bail!("load_thresholds: tiers must be strictly ascending");
+		}
+		if let Some(first) = self.load_thresholds.first()
+			&& self.load_reset >= first.above
+		{
+			bail!("load_thresholds: reset threshold {} must be below the first tier ({})", self.load_reset, first.above);
+		}
+		if self.sample_interval.is_zero() {
+			bail!("sample_interval must be positive");
+		}
+		if self.sustain.is_zero() {
+			bail!("sustain must be positive");
+		}
+		if self.systemd_interval.is_zero() {
+			bail!("systemd_interval must be positive");
+		}
+		if self.systemd_restart_threshold == 0 {
+			bail!("systemd_restart_threshold must be at least 1");
+		}
+
+		if let (Some(horizon), Some(reset)) = (self.eta_horizon, self.eta_reset)
+			&& reset < horizon
+		{
+			bail!("eta_reset: must not be below eta_horizon ({})", format_duration(horizon));
+		}
+		if self.disk_stat_timeout.is_zero() {
+			bail!("disk_stat_timeout must be positive");
+		}
+		for (mount_point, mount) in &self.disk_mounts {
+			let ctx = |what: &str| format!("disk_mounts.\"{mount_point}\".{what}");
+			validate_tiers(&ctx("thresholds"), self.disk_thresholds(mount), self.disk_reset(mount))?;
+			validate_tiers(&ctx("inode_thresholds"), self.inode_thresholds(mount), self.inode_reset(mount))?;
+			validate_free_tiers(&ctx("min_free"), self.disk_min_free(mount), self.disk_min_free_reset(mount))?;
+			if let Some(severity) = &mount.severity {
+				severity.validate(&ctx("severity"))?;
+			}
+		}
+
+		if self.processes_interval.is_zero() {
+			bail!("processes_interval must be positive");
+		}
+		for (i, process) in self.processes.iter().enumerate() {
+			if self.processes[..i].iter().any(|p| p.name == process.name) {
+				bail!("processes.\"{}\": names must be unique", process.name);
+			}
+			if let Err(e) = process.matcher() {
+				bail!("processes.\"{}\": {e}", process.name);
+			}
+		}
+
+		if self.endpoints_interval.is_zero() {
+			bail!("endpoints_interval must be positive");
+		}
+		for (i, endpoint) in self.endpoints.iter().enumerate() {
+			if self.endpoints[..i].iter().any(|e| e.name == endpoint.name) {
+				bail!("endpoints.\"{}\": names must be unique", endpoint.name);
+			}
+			if endpoint.timeout.is_zero() {
+				bail!("endpoints.\"{}\": timeout must be positive", endpoint.name);
+			}
+			if endpoint.failures == 0 {
+				bail!("endpoints.\"{}\": failures must be at least 1", endpoint.name);
+			}
+			if let Err(e) = endpoint.probe() {
+				bail!("endpoints.\"{}\": {e}", endpoint.name);
+			}
+		}
+
+		for dir in &self.watched_dirs {
+			if let Some(reset) = dir.reset
+				&& reset > dir.max_size
+			{
+				bail!("watched_dirs.\"{}\": reset size {reset} must not be over max_size ({})", dir.path, dir.max_size);
+			}
+			if let Err(e) = dir.exclude_set() {
+				bail!("watched_dirs.\"{}\".exclude: {e}", dir.path);
+			}
+			if let Err(e) = dir.expanded_path() {
+				bail!("watched_dirs.\"{}\".path: {e}", dir.path);
+			}
+		}
+		Ok(())
+	}
+
+	pub fn disk_thresholds<'a>(&'a self, mount: &'a MountConfig) -> &'a [u8] {
+		mount.thresholds.as_deref().unwrap_or(&self.disk_thresholds)
+	}
+
+	pub fn disk_reset(&self, mount: &MountConfig) -> u8 {
+		mount.reset.unwrap_or(self.disk_reset)
+	}
+
+	pub fn disk_min_free<'a>(&'a self, mount: &'a MountConfig) -> &'a [FreeSpaceTier] {
+		mount.min_free.as_deref().unwrap_or(&self.disk_min_free)
+	}
+
+	pub fn disk_min_free_reset(&self, mount: &MountConfig) -> Option<InfoSize> {
+		mount.min_free_reset.or(self.disk_min_free_reset)
+	}
+
+	pub fn inode_thresholds<'a>(&'a self, mount: &'a MountConfig) -> &'a [u8] {
+		mount.inode_thresholds.as_deref().unwrap_or(&self.inode_thresholds)
+	}
+
+	pub fn inode_reset(&self, mount: &MountConfig) -> u8 {
+		mount.inode_reset.unwrap_or(self.inode_reset)
+	}
+}
+
+fn validate_tiers(name: &str, tiers: &[u8], reset: u8) -> Result<()> {
+	if let Some(t) = tiers.iter().find(|&&t| t > 100) {
+		bail!("{name}: {t}% is not a percentage");
+	}
+	if !tiers.is_sorted_by(|a, b| a < b) {
+		bail!("{name}: tiers must be strictly ascending, got {tiers:?}");
+	}
+	if let Some(&first) = tiers.first()
+		&& reset >= first
+	{
+		bail!("{name}: reset threshold {reset}% must be below the first tier ({first}%)");
+	}
+	Ok(())
+}
+
+fn validate_free_tiers(name: &str, tiers: &[FreeSpaceTier], reset: Option<InfoSize>) -> Result<()> {
+	if !tiers.is_sorted_by(|a, b| a.below > b.below) {
+		bail!("{name}: tiers must be strictly descending");
+	}
+	if let (Some(first), Some(reset)) = (tiers.first(), reset)
+		&& reset < first.below
+	{
+		bail!("{name}: reset threshold {reset} must not be below the first tier ({})", first.below);
+	}
+	Ok(())
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct FreeSpaceTier {
+	/// Alert when free space drops below this
+	pub below: InfoSize,
+	#[serde(default)]
+	pub severity: Severity,
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct LoadTier {
+	/// Alert when load per CPU stays at or over this
+	pub above: f64,
+	#[serde(default)]
+	pub severity: Severity,
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct WatchedDir {
+	/// A leading `~` or `~/` is expanded to the home directory; also serves as the alert's subject
+	pub path: String,
+	/// Check ID of its alerts, for `routes`; defaults to "dir:{path}"
+	#[serde(default)]
+	pub id: Option<String>,
+	/// Alert when the directory grows over this (e.g., "20GB", "500MB")
 	pub max_size: InfoSize,
+	/// Size to shrink below for the alert to resolve; defaults to `max_size`
+	#[serde(default)]
+	pub reset: Option<InfoSize>,
+	#[serde(default)]
+	pub severity: Severity,
+	/// Remind about the directory being over `max_size` this often while it stays so (e.g. "24h"); never by default
+	#[serde(default, with = "humantime_serde")]
+	pub renotify: Option<Duration>,
+	/// Globs of paths relative to the directory to leave out of the size (e.g. "*.log", "cache/**")
+	#[serde(default)]
+	pub exclude: Vec<String>,
+	/// Don't descend into other filesystems mounted inside the directory
+	#[serde(default)]
+	pub one_file_system: bool,
+	/// Keep an in-memory index of the sizes, updated from change events, instead of walking the whole tree on every check.
+	/// Costs memory per file; events come from fanotify on the whole filesystem where permitted, otherwise from an inotify watch per directory.
+	#[serde(default)]
+	pub incremental: bool,
+	/// How often to check this directory; defaults to `interval`
+	#[serde(default, with = "humantime_serde")]
+	pub interval: Option<Duration>,
+}
+
+impl WatchedDir {
+	/// `path` with `~` expanded
+	pub fn expanded_path(&self) -> Result<PathBuf> {
+		let home = || dirs::home_dir().ok_or_else(|| eyre!("Could not determine home directory"));
+		match self.path.strip_prefix('~') {
+			Some("") => home(),
+			Some(rest) if rest.starts_with('/') => Ok(home()?.join(rest.trim_start_matches('/'))),
+			Some(_) => bail!("only `~` and `~/...` are expanded, other users' home directories (`~user`) aren't"),
+			None => Ok(PathBuf::from(&self.path)),
+		}
+	}
+
+	pub fn check_id(&self) -> String {
+		self.id.clone().unwrap_or_else(|| format!("dir:{}", self.path))
+	}
+
+	pub fn exclude_set(&self) -> Result<GlobSet> {
+		let mut builder = GlobSetBuilder::new();
+		for pattern in &self.exclude {
+			builder.add(Glob::new(pattern)?);
+		}
+		Ok(builder.build()?)
+	}
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct ProcessRule {
+	/// Subject of the alerts; also what the process is found by, unless `cmdline` or `pidfile` is given
+	pub name: String,
+	/// Find it by a regex on its command line instead, arguments joined by spaces (e.g. "^python3 .*worker.py")
+	#[serde(default)]
+	pub cmdline: Option<String>,
+	/// Find it by the PID in this file instead
+	#[serde(default)]
+	pub pidfile: Option<String>,
+	/// Severity of it not running
+	#[serde(default = "__default_critical_severity")]
+	pub severity: Severity,
+	/// Ceilings on resident memory (e.g. "2GB"), CPU usage (% of one core) and open file descriptors; each applies to every matching process
+	#[serde(default)]
+	pub max_rss: Option<InfoSize>,
+	#[serde(default)]
+	pub max_cpu: Option<f64>,
+	#[serde(default)]
+	pub max_fds: Option<u64>,
+	/// Severity of going over a ceiling
+	#[serde(default)]
+	pub limit_severity: Severity,
+}
+
+impl ProcessRule {
+	pub fn matcher(&self) -> Result<ProcessMatcher> {
+		match (&self.cmdline, &self.pidfile) {
+			(Some(_), Some(_)) => bail!("`cmdline` and `pidfile` are mutually exclusive"),
+			(Some(cmdline), None) => Ok(ProcessMatcher::Cmdline(Regex::new(cmdline)?)),
+			(None, Some(pidfile)) => Ok(ProcessMatcher::Pidfile(PathBuf::from(pidfile))),
+			(None, None) => Ok(ProcessMatcher::Name(self.name.clone())),
+		}
+	}
+}
+
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct Endpoint {
+	/// Subject of the alerts
+	pub name: String,
+	/// "http://localhost:8080/health" (or https) to GET it, "tcp://localhost:5432" to only connect
+	pub url: String,
+	/// Status the response must have; any 2xx by default. HTTP only.
+	#[serde(default)]
+	pub status: Option<u16>,
+	/// Regex the response body must match. HTTP only.
+	#[serde(default)]
+	pub body: Option<String>,
+	/// Counts as down when it takes longer than this to pass
+	#[serde(default = "__default_endpoint_timeout", with = "humantime_serde")]
+	pub timeout: Duration,
+	/// Severity of being down
+	#[serde(default = "__default_critical_severity")]
+	pub severity: Severity,
+	/// Consecutive probes that must fail before it's alerted on as down, so a single dropped connection doesn't page anyone
+	#[serde(default = "__default_endpoint_failures")]
+	pub failures: u32,
+	/// Alert when it's up, but passing takes longer than this (e.g. "500ms")
+	#[serde(default, with = "humantime_serde")]
+	pub max_latency: Option<Duration>,
+	#[serde(default)]
+	pub latency_severity: Severity,
+}
+
+impl Endpoint {
+	pub fn probe(&self) -> Result<Probe> {
+		if let Some(addr) = self.url.strip_prefix("tcp://") {
+			if self.status.is_some() || self.body.is_some() {
+				bail!("`status` and `body` only apply to HTTP endpoints");
+			}
+			return Ok(Probe::Tcp(addr.trim_end_matches('/').to_owned()));
+		}
+		if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+			bail!("url must start with http://, https:// or tcp://");
+		}
+		Ok(Probe::Http {
+			url: self.url.clone(),
+			status: self.status,
+			body: self.body.as_deref().map(Regex::new).transpose()?,
+		})
+	}
+}
+
+#[derive(Clone, Debug, Default, MyConfigPrimitives)]
+pub struct MountConfig {
+	#[serde(default)]
+	pub ignore: bool,
+	/// Check the mount even though it's of a pseudo or network filesystem type ([NETWORK_FS_TYPES](crate::mounts::NETWORK_FS_TYPES)), which are skipped by default
+	#[serde(default)]
+	pub include: bool,
+	/// Overrides `disk_thresholds`
+	#[serde(default)]
+	pub thresholds: Option<Vec<u8>>,
+	/// Overrides `disk_reset`
+	#[serde(default)]
+	pub reset: Option<u8>,
+	/// Overrides `disk_severity`
+	#[serde(default)]
+	pub severity: Option<SeverityTiers>,
+	/// Overrides `disk_min_free`
+	#[serde(default)]
+	pub min_free: Option<Vec<FreeSpaceTier>>,
+	/// Overrides `disk_min_free_reset`
+	#[serde(default)]
+	pub min_free_reset: Option<InfoSize>,
+	/// Overrides `inode_thresholds`
+	#[serde(default)]
+	pub inode_thresholds: Option<Vec<u8>>,
+	/// Overrides `inode_reset`
+	#[serde(default)]
+	pub inode_reset: Option<u8>,
 }
 
 impl Default for MonitorConfig {
 	fn default() -> Self {
-		Self { max_size: __default_max_size() }
+		Self {
+			interval: __default_interval(),
+			watched_dirs: __default_watched_dirs(),
+			top_consumers: __default_top_consumers(),
+			walk_threads: 0,
+			reconcile_interval: __default_reconcile_interval(),
+			disk_thresholds: __default_disk_thresholds(),
+			disk_reset: __default_disk_reset(),
+			disk_severity: SeverityTiers::default(),
+			disk_min_free: Vec::new(),
+			disk_min_free_reset: None,
+			inode_thresholds: __default_inode_thresholds(),
+			inode_reset: __default_inode_reset(),
+			inode_severity: __default_inode_severity(),
+			memory_thresholds: __default_memory_thresholds(),
+			memory_reset: __default_memory_reset(),
+			memory_severity: SeverityTiers::default(),
+			swap_thresholds: __default_swap_thresholds(),
+			swap_reset: __default_swap_reset(),
+			swap_severity: __default_swap_severity(),
+			memory_pressure_thresholds: __default_memory_pressure_thresholds(),
+			memory_pressure_reset: __default_memory_pressure_reset(),
+			memory_pressure_severity: __default_memory_pressure_severity(),
+			sample_interval: __default_sample_interval(),
+			sustain: __default_sustain(),
+			load_thresholds: __default_load_thresholds(),
+			load_reset: __default_load_reset(),
+			cpu_pressure_thresholds: __default_cpu_pressure_thresholds(),
+			cpu_pressure_reset: __default_cpu_pressure_reset(),
+			cpu_pressure_severity: __default_cpu_pressure_severity(),
+			io_pressure_thresholds: __default_io_pressure_thresholds(),
+			io_pressure_reset: __default_io_pressure_reset(),
+			io_pressure_severity: __default_io_pressure_severity(),
+			kernel_log: true,
+			kernel_log_cooldown: __default_kernel_log_cooldown(),
+			oom_severity: __default_critical_severity(),
+			hung_task_severity: Severity::default(),
+			fs_error_severity: __default_critical_severity(),
+			systemd: true,
+			systemd_interval: __default_check_interval(),
+			systemd_severity: __default_critical_severity(),
+			systemd_restart_threshold: __default_systemd_restart_threshold(),
+			systemd_restart_window: __default_systemd_restart_window(),
+			systemd_restart_severity: Severity::default(),
+			systemd_journal_lines: __default_systemd_journal_lines(),
+			processes: Vec::new(),
+			processes_interval: __default_check_interval(),
+			endpoints: Vec::new(),
+			endpoints_interval: __default_check_interval(),
+			eta_horizon: None,
+			eta_reset: None,
+			eta_window: __default_eta_window(),
+			eta_severity: __default_critical_severity(),
+			disk_include_pseudo: false,
+			disk_ignore_fs_types: Vec::new(),
+			disk_mounts: BTreeMap::new(),
+			disk_stat_timeout: __default_disk_stat_timeout(),
+			disk_unresponsive_severity: __default_critical_severity(),
+			max_size: None,
+			max_size_severity: None,
+			max_size_reset: None,
+			max_size_renotify: None,
+		}
+	}
+}
+
+/// Lowest threshold tier (in %) from which alerts get each severity; tiers below `warning` are `info`
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct SeverityTiers {
+	pub warning: u8,
+	pub critical: u8,
+	pub emergency: u8,
+}
+
+impl SeverityTiers {
+	pub fn validate(&self, name: &str) -> Result<()> {
+		if !(self.warning <= self.critical && self.critical <= self.emergency) {
+			bail!(
+				"{name}: tiers must be ascending from warning to emergency, got {}/{}/{}",
+				self.warning,
+				self.critical,
+				self.emergency
+			);
+		}
+		Ok(())
+	}
+
+	pub fn severity(&self, tier: u8) -> Severity {
+		match tier {
+			t if t >= self.emergency => Severity::Emergency,
+			t if t >= self.critical => Severity::Critical,
+			t if t >= self.warning => Severity::Warning,
+			_ => Severity::Info,
+		}
+	}
+}
+
+impl Default for SeverityTiers {
+	fn default() -> Self {
+		Self {
+			warning: 70,
+			critical: 90,
+			emergency: 95,
+		}
+	}
+}
+
+fn __default_interval() -> Duration {
+	Duration::from_secs(60 * 60) // 1 hour
+}
+
+fn __default_reconcile_interval() -> Duration {
+	Duration::from_secs(24 * 60 * 60)
+}
+
+fn __default_top_consumers() -> usize {
+	5
+}
+
+fn __default_disk_thresholds() -> Vec<u8> {
+	vec![50, 60, 70, 80, 90, 95]
+}
+
+fn __default_disk_reset() -> u8 {
+	45
+}
+
+fn __default_inode_thresholds() -> Vec<u8> {
+	vec![80, 90, 95, 99]
+}
+
+fn __default_inode_reset() -> u8 {
+	75
+}
+
+fn __default_inode_severity() -> SeverityTiers {
+	SeverityTiers {
+		warning: 80,
+		critical: 90,
+		emergency: 95,
 	}
 }
 
-fn __default_max_size() -> InfoSize {
-	InfoSize::from_parts(10, v_utils::utils::InfoSizeUnit::Gigabyte)
+fn __default_memory_thresholds() -> Vec<u8> {
+	vec![80, 90, 95]
+}
+
+fn __default_memory_reset() -> u8 {
+	75
+}
+
+fn __default_swap_thresholds() -> Vec<u8> {
+	vec![50, 80, 95]
+}
+
+fn __default_swap_reset() -> u8 {
+	40
+}
+
+fn __default_swap_severity() -> SeverityTiers {
+	SeverityTiers {
+		warning: 50,
+		critical: 80,
+		emergency: 95,
+	}
+}
+
+fn __default_memory_pressure_thresholds() -> Vec<u8> {
+	vec![10, 20, 40]
+}
+
+fn __default_memory_pressure_reset() -> u8 {
+	5
+}
+
+fn __default_memory_pressure_severity() -> SeverityTiers {
+	SeverityTiers {
+		warning: 10,
+		critical: 20,
+		emergency: 40,
+	}
+}
+
+fn __default_sample_interval() -> Duration {
+	Duration::from_secs(30)
+}
+
+fn __default_sustain() -> Duration {
+	Duration::from_secs(10 * 60)
+}
+
+fn __default_load_thresholds() -> Vec<LoadTier> {
+	vec![
+		LoadTier {
+			above: 1.0,
+			severity: Severity::Info,
+		},
+		LoadTier {
+			above: 2.0,
+			severity: Severity::Warning,
+		},
+		LoadTier {
+			above: 4.0,
+			severity: Severity::Critical,
+		},
+	]
+}
+
+fn __default_load_reset() -> f64 {
+	0.8
+}
+
+fn __default_cpu_pressure_thresholds() -> Vec<u8> {
+	vec![25, 50, 80]
+}
+
+fn __default_cpu_pressure_reset() -> u8 {
+	10
+}
+
+fn __default_cpu_pressure_severity() -> SeverityTiers {
+	SeverityTiers {
+		warning: 25,
+		critical: 50,
+		emergency: 80,
+	}
+}
+
+fn __default_io_pressure_thresholds() -> Vec<u8> {
+	vec![30, 60, 90]
+}
+
+fn __default_io_pressure_reset() -> u8 {
+	15
+}
+
+fn __default_io_pressure_severity() -> SeverityTiers {
+	SeverityTiers {
+		warning: 30,
+		critical: 60,
+		emergency: 90,
+	}
+}
+
+fn __default_kernel_log_cooldown() -> Duration {
+	Duration::from_secs(5 * 60)
+}
+
+fn __default_critical_severity() -> Severity {
+	Severity::Critical
+}
+
+/// For the checks that run on their own schedule
+fn __default_check_interval() -> Duration {
+	Duration::from_secs(60)
+}
+
+fn __default_systemd_restart_threshold() -> u32 {
+	3
+}
+
+fn __default_systemd_restart_window() -> Duration {
+	Duration::from_secs(10 * 60)
+}
+
+fn __default_systemd_journal_lines() -> usize {
+	10
+}
+
+fn __default_endpoint_timeout() -> Duration {
+	Duration::from_secs(10)
+}
+
+fn __default_endpoint_failures() -> u32 {
+	2
+}
+
+fn __default_eta_window() -> Duration {
+	Duration::from_secs(6 * 60 * 60)
+}
+
+fn __default_disk_stat_timeout() -> Duration {
+	Duration::from_secs(10)
+}
+
+/// Check ID of the default watched directory, as it was before there could be several
+pub const STATE_DIR_CHECK: &str = "state_dir";
+
+fn __default_watched_dirs() -> Vec<WatchedDir> {
+	vec![WatchedDir {
+		// honours $XDG_STATE_HOME
+		path: dirs::state_dir().map_or_else(|| "~/.local/state".to_owned(), |dir| dir.to_string_lossy().into_owned()),
+		id: Some(STATE_DIR_CHECK.to_owned()),
+		max_size: InfoSize::from_parts(10, v_utils::utils::InfoSizeUnit::Gigabyte),
+		reset: None,
+		severity: Severity::default(),
+		renotify: None,
+		exclude: Vec::new(),
+		one_file_system: false,
+		incremental: false,
+		interval: None,
+	}]
 }
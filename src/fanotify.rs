@@ -0,0 +1,164 @@
+//! Filesystem-wide change notifications from fanotify, for size indexes too big to watch directory by directory with inotify.
+use std::{
+	collections::{BTreeSet, HashMap},
+	ffi::{CStr, CString, OsStr},
+	fs, io,
+	mem::size_of,
+	os::{
+		fd::{AsRawFd, FromRawFd, OwnedFd},
+		unix::ffi::OsStrExt,
+	},
+	path::{Path, PathBuf},
+	ptr,
+};
+
+use libc::{fanotify_event_info_fid, fanotify_event_info_header, fanotify_event_metadata};
+
+const MASK: u64 = libc::FAN_CREATE | libc::FAN_DELETE | libc::FAN_MODIFY | libc::FAN_MOVED_FROM | libc::FAN_MOVED_TO | libc::FAN_ONDIR;
+
+/// Changes anywhere on one filesystem, reported as the directory they happened in plus the name of the entry
+pub struct Fanotify {
+	fd: OwnedFd,
+	/// Any open directory on the filesystem, for resolving the handles events refer to directories by
+	mount: OwnedFd,
+}
+
+/// What one read of the queue brought
+#[derive(Debug, Default)]
+pub struct Changes {
+	/// Entries created, deleted, written to or moved, as paths
+	pub paths: BTreeSet<PathBuf>,
+	/// The kernel dropped events, so anything may have changed
+	pub overflowed: bool,
+}
+
+impl Fanotify {
+	/// Marks the whole filesystem `path` is on. Takes CAP_SYS_ADMIN, and CAP_DAC_READ_SEARCH to turn what's reported back into paths, so fails for anyone but root.
+	pub fn watch_filesystem(path: &Path) -> io::Result<Self> {
+		// SAFETY: takes no pointers; the returned descriptor is checked before use
+		let fd = unsafe { libc::fanotify_init(libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC | libc::FAN_REPORT_DFID_NAME, libc::O_RDONLY as u32) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		// SAFETY: just created, and owned by nothing else
+		let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+		let c_path = CString::new(path.as_os_str().as_bytes())?;
+		// SAFETY: `c_path` is a valid NUL-terminated string that outlives the call
+		let ret = unsafe { libc::fanotify_mark(fd.as_raw_fd(), libc::FAN_MARK_ADD | libc::FAN_MARK_FILESYSTEM, MASK, libc::AT_FDCWD, c_path.as_ptr()) };
+		if ret < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		let mount = fs::File::open(path)?.into();
+		let fanotify = Self { fd, mount };
+		// resolving handles is what's most likely to be missing, so better find out now than on the first event
+		fanotify.resolve(&fanotify.handle_of(path)?)?;
+		Ok(fanotify)
+	}
+
+	/// Blocks until there are events, and resolves them to paths. Directories removed since can't be resolved anymore; their own removal is reported in their parent.
+	pub fn read_changes(&self, buffer: &mut [u8]) -> io::Result<Changes> {
+		// SAFETY: writes at most `buffer.len()` bytes into `buffer`
+		let len = unsafe { libc::read(self.fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+		if len < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		let events = &buffer[..len as usize];
+
+		let mut changes = Changes::default();
+		// a burst of events tends to be in a few directories
+		let mut dirs: HashMap<&[u8], Option<PathBuf>> = HashMap::new();
+		let mut offset = 0;
+		while let Some(metadata) = read::<fanotify_event_metadata>(events, offset) {
+			let event_len = metadata.event_len as usize;
+			if event_len < size_of::<fanotify_event_metadata>() || offset + event_len > events.len() {
+				break;
+			}
+			if metadata.mask & libc::FAN_Q_OVERFLOW != 0 {
+				changes.overflowed = true;
+			}
+			if metadata.fd >= 0 {
+				// SAFETY: the kernel opened it for us, and nothing else refers to it
+				drop(unsafe { OwnedFd::from_raw_fd(metadata.fd) });
+			}
+
+			let event = &events[offset..offset + event_len];
+			let mut info = metadata.metadata_len as usize;
+			while let Some(header) = read::<fanotify_event_info_header>(event, info) {
+				let record_len = header.len as usize;
+				if record_len == 0 || info + record_len > event.len() {
+					break;
+				}
+				if header.info_type == libc::FAN_EVENT_INFO_TYPE_DFID_NAME
+					&& let Some((handle, name)) = split_dfid_name(&event[info..info + record_len])
+				{
+					let dir = dirs.entry(handle).or_insert_with(|| self.resolve(handle).ok());
+					if let Some(dir) = dir {
+						changes.paths.insert(match name.to_bytes() {
+							// the directory itself
+							b"." => dir.clone(),
+							name => dir.join(OsStr::from_bytes(name)),
+						});
+					}
+				}
+				info += record_len;
+			}
+			offset += event_len;
+		}
+		Ok(changes)
+	}
+
+	/// Path of the directory a `struct file_handle` (as found in events) refers to
+	fn resolve(&self, handle: &[u8]) -> io::Result<PathBuf> {
+		// open_by_handle_at wants it aligned
+		let mut aligned = vec![0u64; handle.len().div_ceil(size_of::<u64>())];
+		// SAFETY: `aligned` holds at least `handle.len()` bytes, and doesn't overlap `handle`
+		unsafe { ptr::copy_nonoverlapping(handle.as_ptr(), aligned.as_mut_ptr().cast::<u8>(), handle.len()) };
+		// SAFETY: `aligned` holds a whole file_handle, its `handle_bytes` included, as the kernel reported it
+		let fd = unsafe { libc::open_by_handle_at(self.mount.as_raw_fd(), aligned.as_mut_ptr().cast(), libc::O_PATH | libc::O_CLOEXEC) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		// SAFETY: just opened, and owned by nothing else
+		let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+		fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+	}
+
+	/// The handle of `path`, as events would refer to it
+	fn handle_of(&self, path: &Path) -> io::Result<Vec<u8>> {
+		const MAX_HANDLE_SZ: usize = 128;
+		let c_path = CString::new(path.as_os_str().as_bytes())?;
+		let mut handle = vec![0u64; (size_of::<libc::file_handle>() + MAX_HANDLE_SZ).div_ceil(size_of::<u64>())];
+		let file_handle = handle.as_mut_ptr().cast::<libc::file_handle>();
+		let mut mount_id = 0;
+		// SAFETY: `handle` is big enough for any handle, as its `handle_bytes` tells the kernel; `c_path` outlives the call
+		let ret = unsafe {
+			(*file_handle).handle_bytes = MAX_HANDLE_SZ as u32;
+			libc::name_to_handle_at(libc::AT_FDCWD, c_path.as_ptr(), file_handle, &mut mount_id, 0)
+		};
+		if ret < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		// SAFETY: filled in by the kernel above
+		let len = size_of::<libc::file_handle>() + unsafe { (*file_handle).handle_bytes } as usize;
+		// SAFETY: `handle` spans at least `len` bytes
+		Ok(unsafe { std::slice::from_raw_parts(handle.as_ptr().cast::<u8>(), len) }.to_vec())
+	}
+}
+
+/// A DFID_NAME record is the header, the filesystem ID, a `struct file_handle` of the directory and the NUL-terminated entry name
+fn split_dfid_name(record: &[u8]) -> Option<(&[u8], &CStr)> {
+	let handle_start = size_of::<fanotify_event_info_fid>();
+	let handle_bytes = read::<u32>(record, handle_start)? as usize;
+	let name_start = handle_start + size_of::<libc::file_handle>() + handle_bytes;
+	let handle = record.get(handle_start..name_start)?;
+	let name = CStr::from_bytes_until_nul(record.get(name_start..)?).ok()?;
+	Some((handle, name))
+}
+
+/// A `T` at `offset` of `bytes`, which needn't be aligned for it; None past the end
+fn read<T: Copy>(bytes: &[u8], offset: usize) -> Option<T> {
+	let bytes = bytes.get(offset..offset.checked_add(size_of::<T>())?)?;
+	// SAFETY: `bytes` spans a whole `T`, and only plain C structs and integers are read this way
+	Some(unsafe { ptr::read_unaligned(bytes.as_ptr().cast()) })
+}
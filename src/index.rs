@@ -0,0 +1,430 @@
+//! In-memory size index of a directory, kept up to date from fanotify or inotify events instead of re-walking it.
+use std::{
+	cmp::Reverse,
+	collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
+	fs,
+	os::unix::fs::MetadataExt,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, Weak},
+};
+
+use color_eyre::eyre::Result;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask, Watches};
+use tracing::{debug, error, warn};
+
+use crate::{
+	fanotify::Fanotify,
+	throttle::RateLimiter,
+	walk::{DirUsage, WalkOptions},
+};
+
+const WATCH_MASK: WatchMask = WatchMask::CREATE
+	.union(WatchMask::DELETE)
+	.union(WatchMask::MODIFY)
+	.union(WatchMask::MOVED_FROM)
+	.union(WatchMask::MOVED_TO)
+	.union(WatchMask::DONT_FOLLOW)
+	.union(WatchMask::ONLYDIR)
+	.union(WatchMask::EXCL_UNLINK);
+
+/// Sizes of every file under a directory, updated as they change.
+///
+/// Where fanotify is permitted, changes come from a mark on the root's whole filesystem, which isn't bound by the inotify watch limit on huge trees; directories on other filesystems, and everything elsewhere, are watched one by one with inotify.
+///
+/// Clones share the same index. Same as [dir_usage](crate::walk::dir_usage), symlinks are not followed and hardlinks are counted once.
+#[derive(Clone)]
+pub struct SizeIndex(Arc<Mutex<Index>>);
+
+impl SizeIndex {
+	/// Does the initial full scan of `root`, then keeps the index current from background threads
+	pub fn watch(root: &Path, options: &WalkOptions) -> Result<Self> {
+		let fanotify = Fanotify::watch_filesystem(root)
+			.inspect_err(|e| debug!("Can't follow the filesystem of {} with fanotify, watching directories with inotify instead: {e}", root.display()))
+			.ok();
+		Self::watch_with(root, options, fanotify)
+	}
+
+	/// Same as [watch](Self::watch), but with inotify alone even where fanotify is permitted
+	pub fn watch_inotify(root: &Path, options: &WalkOptions) -> Result<Self> {
+		Self::watch_with(root, options, None)
+	}
+
+	fn watch_with(root: &Path, options: &WalkOptions, fanotify: Option<Fanotify>) -> Result<Self> {
+		let inotify = Inotify::init()?;
+		let root_meta = fs::symlink_metadata(root)?;
+		// what fanotify reports paths relative to
+		let canonical_root = fs::canonicalize(root)?;
+		let mut scanner = Scanner {
+			root: root.to_owned(),
+			device: options.one_file_system.then_some(root_meta.dev()),
+			fs_wide: fanotify.as_ref().map(|_| root_meta.dev()),
+			options: options.clone(),
+			watches: inotify.watches(),
+			limiter: Arc::new(RateLimiter::new(options.entries_per_sec)),
+		};
+		let tree = scanner.scan_all();
+		let mut index = Index {
+			scanner,
+			tree: Tree::default(),
+			scans_underway: 0,
+			changed_while_scanning: BTreeSet::new(),
+		};
+		index.swap_in(tree);
+
+		let index = Arc::new(Mutex::new(index));
+		let weak = Arc::downgrade(&index);
+		std::thread::Builder::new().name("size-index".to_owned()).spawn(move || follow_events(inotify, weak))?;
+		if let Some(fanotify) = fanotify {
+			let weak = Arc::downgrade(&index);
+			let root = root.to_owned();
+			std::thread::Builder::new()
+				.name("size-index-fs".to_owned())
+				.spawn(move || follow_filesystem(&fanotify, &canonical_root, &root, weak))?;
+		}
+		Ok(Self(index))
+	}
+
+	/// Whether changes come from a fanotify mark on the whole filesystem rather than from inotify watches alone
+	pub fn filesystem_wide(&self) -> bool {
+		self.0.lock().unwrap().scanner.fs_wide.is_some()
+	}
+
+	pub fn usage(&self, top_n: usize) -> DirUsage {
+		self.0.lock().unwrap().usage(top_n)
+	}
+
+	/// Rescans the whole tree to correct whatever drift the events missed; returns by how many bytes the index was off
+	pub fn reconcile(&self) -> i64 {
+		rescan(&self.0)
+	}
+}
+
+/// Scans the tree without holding the index locked, so that usage can still be read and events applied in the meantime, then swaps the result in
+fn rescan(index: &Mutex<Index>) -> i64 {
+	let mut scanner = {
+		let mut index = index.lock().unwrap();
+		index.scans_underway += 1;
+		index.scanner.clone()
+	};
+	let tree = scanner.scan_all();
+
+	let (before, changed) = {
+		let mut index = index.lock().unwrap();
+		let before = index.usage(0).apparent;
+		index.swap_in(tree);
+		index.scans_underway -= 1;
+		// another rescan still underway may have read them before the change as well
+		let changed = match index.scans_underway {
+			0 => std::mem::take(&mut index.changed_while_scanning),
+			_ => index.changed_while_scanning.clone(),
+		};
+		(before, changed)
+	};
+	// the scan may have read these before they changed
+	let scanned = scanner.scan_paths(changed);
+
+	let mut index = index.lock().unwrap();
+	index.apply(scanned);
+	index.usage(0).apparent as i64 - before as i64
+}
+
+/// What it takes to scan the tree; cloned out of the index for full rescans
+#[derive(Clone)]
+struct Scanner {
+	root: PathBuf,
+	/// Only descend into directories on this device
+	device: Option<u64>,
+	/// Device fanotify already reports every change on, so its directories need no inotify watch
+	fs_wide: Option<u64>,
+	options: WalkOptions,
+	watches: Watches,
+	/// Shared by all scans over the life of the index, so that refreshes in quick succession are paced as one
+	limiter: Arc<RateLimiter>,
+}
+
+/// Everything found under the root
+#[derive(Default)]
+struct Tree {
+	files: BTreeMap<PathBuf, FileEntry>,
+	dirs: BTreeMap<PathBuf, WatchDescriptor>,
+	dir_paths: HashMap<WatchDescriptor, PathBuf>,
+	/// Entries that couldn't be read
+	errors: u64,
+	/// Directories that couldn't be watched, usually due to fs.inotify.max_user_watches
+	unwatched: u64,
+}
+
+struct FileEntry {
+	dev: u64,
+	ino: u64,
+	apparent: u64,
+	allocated: u64,
+}
+
+impl Scanner {
+	fn scan_all(&mut self) -> Tree {
+		let mut tree = Tree::default();
+		let root = self.root.clone();
+		match fs::symlink_metadata(&root) {
+			Ok(meta) => self.scan(&mut tree, root, &meta),
+			Err(e) => {
+				error!("Failed to index {}: {e}", root.display());
+				tree.errors += 1;
+			}
+		}
+		tree
+	}
+
+	fn scan(&mut self, tree: &mut Tree, dir: PathBuf, meta: &fs::Metadata) {
+		if self.device.is_some_and(|dev| meta.dev() != dev) {
+			return;
+		}
+		// watch before reading, so nothing created in between is missed
+		if self.fs_wide != Some(meta.dev()) {
+			match self.watches.add(&dir, WATCH_MASK) {
+				Ok(wd) => {
+					tree.dir_paths.insert(wd.clone(), dir.clone());
+					tree.dirs.insert(dir.clone(), wd);
+				}
+				Err(_) => tree.unwatched += 1,
+			}
+		}
+		let Ok(entries) = fs::read_dir(&dir) else {
+			tree.errors += 1;
+			return;
+		};
+
+		for entry in entries {
+			self.limiter.entry();
+			let Ok(entry) = entry else {
+				tree.errors += 1;
+				continue;
+			};
+			let path = entry.path();
+			if self.options.excludes(&self.root, &path) {
+				continue;
+			}
+			match fs::symlink_metadata(&path) {
+				Ok(meta) if meta.is_dir() => self.scan(tree, path, &meta),
+				Ok(meta) => tree.insert_file(path, &meta),
+				Err(_) => tree.errors += 1,
+			}
+		}
+	}
+
+	/// Scans each of `paths`, and everything under those that are directories; a path that's gone or excluded comes back with an empty tree
+	fn scan_paths(&mut self, paths: BTreeSet<PathBuf>) -> Vec<(PathBuf, Tree)> {
+		paths
+			.into_iter()
+			.map(|path| {
+				let mut tree = Tree::default();
+				if !self.options.excludes(&self.root, &path) {
+					match fs::symlink_metadata(&path) {
+						Ok(meta) if meta.is_dir() => self.scan(&mut tree, path.clone(), &meta),
+						Ok(meta) => tree.insert_file(path.clone(), &meta),
+						// removed
+						Err(_) => {}
+					}
+				}
+				(path, tree)
+			})
+			.collect()
+	}
+}
+
+impl Tree {
+	fn insert_file(&mut self, path: PathBuf, meta: &fs::Metadata) {
+		let entry = FileEntry {
+			dev: meta.dev(),
+			ino: meta.ino(),
+			apparent: meta.len(),
+			allocated: meta.blocks() * 512,
+		};
+		self.files.insert(path, entry);
+	}
+}
+
+struct Index {
+	scanner: Scanner,
+	tree: Tree,
+	/// Full rescans running, each from a scanner of its own
+	scans_underway: u32,
+	/// Paths changed while any full rescan is underway; they're scanned again once it's swapped in, as it may have read them before the change
+	changed_while_scanning: BTreeSet<PathBuf>,
+}
+
+impl Index {
+	/// Replaces the tree with a freshly scanned one
+	fn swap_in(&mut self, tree: Tree) {
+		let old = std::mem::replace(&mut self.tree, tree);
+		// adding a watch on an already watched directory gives back the same descriptor, so whatever is left is gone
+		for wd in old.dirs.into_values().filter(|wd| !self.tree.dir_paths.contains_key(wd)) {
+			let _ = self.scanner.watches.remove(wd);
+		}
+		if self.tree.unwatched > 0 {
+			warn!(
+				"Couldn't watch {} directories under {}, changes there are only picked up on reconciliation (is fs.inotify.max_user_watches too low?)",
+				self.tree.unwatched,
+				self.scanner.root.display()
+			);
+		}
+	}
+
+	/// Replaces what's indexed under each path with what [Scanner::scan_paths] found there
+	fn apply(&mut self, scanned: Vec<(PathBuf, Tree)>) {
+		// a directory moved within the tree keeps its watch, and scanning it at the new path has already claimed it again
+		let rewatched: HashSet<WatchDescriptor> = scanned.iter().flat_map(|(_, fresh)| fresh.dir_paths.keys().cloned()).collect();
+		for (path, fresh) in scanned {
+			if self.scans_underway > 0 {
+				self.changed_while_scanning.insert(path.clone());
+			}
+			let tree = &mut self.tree;
+			let stale_files: Vec<PathBuf> = tree.files.range(path.clone()..).map(|(p, _)| p).take_while(|p| p.starts_with(&path)).cloned().collect();
+			for p in stale_files {
+				tree.files.remove(&p);
+			}
+			let stale_dirs: Vec<PathBuf> = tree.dirs.range(path.clone()..).map(|(p, _)| p).take_while(|p| p.starts_with(&path)).cloned().collect();
+			for p in stale_dirs {
+				// the new path may have claimed the watch of a moved directory already
+				if let Some(wd) = tree.dirs.remove(&p)
+					&& tree.dir_paths.get(&wd) == Some(&p)
+				{
+					tree.dir_paths.remove(&wd);
+					if !rewatched.contains(&wd) {
+						let _ = self.scanner.watches.remove(wd);
+					}
+				}
+			}
+
+			tree.files.extend(fresh.files);
+			tree.dirs.extend(fresh.dirs);
+			tree.dir_paths.extend(fresh.dir_paths);
+			tree.errors += fresh.errors;
+			tree.unwatched += fresh.unwatched;
+		}
+	}
+
+	/// The kernel dropped the watch, because the directory is gone or unmounted
+	fn forget_watch(&mut self, wd: &WatchDescriptor) {
+		if let Some(path) = self.tree.dir_paths.remove(wd)
+			&& self.tree.dirs.get(&path) == Some(wd)
+		{
+			self.tree.dirs.remove(&path);
+		}
+	}
+
+	fn usage(&self, top_n: usize) -> DirUsage {
+		let mut usage = DirUsage {
+			errors: self.tree.errors,
+			..Default::default()
+		};
+		let mut seen = HashSet::new();
+		let mut dirs: HashMap<PathBuf, u64> = HashMap::new();
+		// min-heap, so the smallest of the current top is the one to evict
+		let mut top_files = BinaryHeap::new();
+
+		for (path, file) in &self.tree.files {
+			if !seen.insert((file.dev, file.ino)) {
+				continue;
+			}
+			usage.apparent += file.apparent;
+			usage.allocated += file.allocated;
+			if top_n == 0 {
+				continue;
+			}
+
+			let mut components = path.strip_prefix(&self.scanner.root).unwrap_or(path).components();
+			if let (Some(first), Some(_)) = (components.next(), components.next()) {
+				*dirs.entry(self.scanner.root.join(first)).or_default() += file.apparent;
+			}
+			top_files.push(Reverse((file.apparent, path)));
+			if top_files.len() > top_n {
+				top_files.pop();
+			}
+		}
+
+		usage.top_dirs = dirs.into_iter().collect();
+		usage.top_dirs.sort_by_key(|(_, size)| Reverse(*size));
+		usage.top_dirs.truncate(top_n);
+		usage.top_files = top_files.into_iter().map(|Reverse((size, path))| (path.clone(), size)).collect();
+		usage.top_files.sort_by_key(|(_, size)| Reverse(*size));
+		usage
+	}
+}
+
+fn follow_events(mut inotify: Inotify, index: Weak<Mutex<Index>>) {
+	let mut buffer = vec![0; 64 * 1024];
+	loop {
+		let events = match inotify.read_events_blocking(&mut buffer) {
+			Ok(events) => events,
+			Err(e) => {
+				error!("Stopped following changes for the size index: {e}");
+				return;
+			}
+		};
+		// every handle to the index is gone
+		let Some(index) = index.upgrade() else {
+			return;
+		};
+
+		// a burst of writes to one file comes as a burst of events, but only needs one look
+		let mut changed = BTreeSet::new();
+		let mut overflowed = false;
+		let scanner = {
+			let mut guard = index.lock().unwrap();
+			for event in events {
+				if event.mask.contains(EventMask::Q_OVERFLOW) {
+					overflowed = true;
+				} else if event.mask.contains(EventMask::IGNORED) {
+					guard.forget_watch(&event.wd);
+				} else if let (Some(dir), Some(name)) = (guard.tree.dir_paths.get(&event.wd), event.name) {
+					changed.insert(dir.join(name));
+				}
+			}
+			guard.scanner.clone()
+		};
+		catch_up(&index, scanner, changed, overflowed);
+	}
+}
+
+/// Same as [follow_events], for what fanotify reports on the whole filesystem; only changes under `canonical_root` are kept, and put back under `root`
+fn follow_filesystem(fanotify: &Fanotify, canonical_root: &Path, root: &Path, index: Weak<Mutex<Index>>) {
+	let mut buffer = vec![0; 64 * 1024];
+	loop {
+		let changes = match fanotify.read_changes(&mut buffer) {
+			Ok(changes) => changes,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => {
+				error!("Stopped following changes for the size index: {e}");
+				return;
+			}
+		};
+		let Some(index) = index.upgrade() else {
+			return;
+		};
+
+		let changed: BTreeSet<PathBuf> = changes
+			.paths
+			.iter()
+			.filter_map(|path| path.strip_prefix(canonical_root).ok())
+			.map(|relative| root.join(relative))
+			.collect();
+		if changed.is_empty() && !changes.overflowed {
+			continue;
+		}
+		let scanner = index.lock().unwrap().scanner.clone();
+		catch_up(&index, scanner, changed, changes.overflowed);
+	}
+}
+
+/// Applies what changed, or rescans the whole tree if the kernel dropped events
+fn catch_up(index: &Mutex<Index>, mut scanner: Scanner, changed: BTreeSet<PathBuf>, overflowed: bool) {
+	if overflowed {
+		warn!("Missed changes under {}, rescanning it", scanner.root.display());
+		rescan(index);
+	} else {
+		// a big tree moved in takes a while to scan at the rate limit, during which usage can still be read
+		let scanned = scanner.scan_paths(changed);
+		index.lock().unwrap().apply(scanned);
+	}
+}
@@ -0,0 +1,199 @@
+//! Kernel log messages, from /dev/kmsg or, where that can't be read, from the journal.
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{self, Read, Seek, SeekFrom},
+	process::Stdio,
+};
+
+use color_eyre::eyre::{Result, eyre};
+use tokio::{
+	io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader},
+	process::Command,
+	sync::mpsc,
+};
+use tracing::{info, warn};
+
+/// Kernel messages that are worth an alert
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KernelEvent {
+	OomKill {
+		pid: u32,
+		process: String,
+		/// Bytes of anonymous, file-backed and shared memory resident when it was killed
+		anon_rss: u64,
+		file_rss: u64,
+		shmem_rss: u64,
+		/// Killed for going over its cgroup's memory limit, rather than the whole system running out
+		cgroup: bool,
+	},
+	HungTask {
+		pid: u32,
+		process: String,
+		/// How long it has been blocked for at least
+		secs: u64,
+	},
+	/// I/O errors and filesystem errors or corruption, with the kernel's message as is
+	FsError(String),
+}
+
+/// Substrings of messages that report a filesystem or block device failing
+const FS_ERROR_MARKERS: &[&str] = &["-fs error", "BTRFS error", "BTRFS critical", "I/O error", "Corruption detected", "Metadata corruption"];
+
+impl KernelEvent {
+	pub fn parse(message: &str) -> Option<Self> {
+		// `Out of memory: Killed process 1234 (java) total-vm:123kB, anon-rss:456kB, file-rss:0kB, shmem-rss:0kB, UID:1000 ...`
+		if let Some((before, after)) = message.split_once("Killed process ") {
+			let (pid, rest) = after.split_once(" (")?;
+			let name_end = rest.find(") total-vm").or_else(|| rest.rfind(')'))?;
+			let kb = |field: &str| -> u64 {
+				let Some((_, value)) = rest.split_once(field) else { return 0 };
+				value.chars().take_while(char::is_ascii_digit).collect::<String>().parse::<u64>().unwrap_or(0) * 1024
+			};
+			return Some(Self::OomKill {
+				pid: pid.trim().parse().ok()?,
+				process: rest[..name_end].to_owned(),
+				anon_rss: kb("anon-rss:"),
+				file_rss: kb("file-rss:"),
+				shmem_rss: kb("shmem-rss:"),
+				cgroup: before.contains("cgroup"),
+			});
+		}
+
+		// `INFO: task kworker/0:1:123 blocked for more than 120 seconds.`
+		// anything else that reads similar falls through, it may still be a filesystem error
+		if let Some((_, after)) = message.split_once("task ")
+			&& let Some((task, rest)) = after.split_once(" blocked for more than ")
+			&& let Some((process, pid)) = task.rsplit_once(':')
+			&& let Ok(pid) = pid.parse()
+			&& let Some(Ok(secs)) = rest.split_whitespace().next().map(str::parse)
+		{
+			return Some(Self::HungTask {
+				pid,
+				process: process.to_owned(),
+				secs,
+			});
+		}
+
+		FS_ERROR_MARKERS.iter().any(|m| message.contains(m)).then(|| Self::FsError(message.trim().to_owned()))
+	}
+}
+
+/// Message of a /dev/kmsg record, which looks like `6,1234,5678901,-;message`, followed by `\n KEY=value` lines of metadata
+pub fn parse_kmsg_record(record: &str) -> Option<&str> {
+	let (_prefix, rest) = record.split_once(';')?;
+	Some(rest.lines().next().unwrap_or_default())
+}
+
+/// Streams kernel log messages from now on. Reads /dev/kmsg if permitted (root or CAP_SYSLOG), else follows `journalctl --dmesg`.
+pub async fn follow_kernel_log() -> Result<mpsc::Receiver<String>> {
+	let (tx, rx) = mpsc::channel(1024);
+	match File::open("/dev/kmsg") {
+		Ok(kmsg) => {
+			info!("Following the kernel log from /dev/kmsg");
+			follow_kmsg(kmsg, tx)?;
+		}
+		Err(e) => {
+			warn!("Can't read /dev/kmsg ({e}), following the kernel log through journalctl instead");
+			follow_journal(tx)?;
+		}
+	}
+	Ok(rx)
+}
+
+fn follow_kmsg(mut kmsg: File, tx: mpsc::Sender<String>) -> Result<()> {
+	// what's already in the ring buffer has been dealt with by whoever was around when it happened
+	kmsg.seek(SeekFrom::End(0))?;
+	std::thread::Builder::new().name("kmsg".to_owned()).spawn(move || {
+		// each read returns exactly one record, which is at most this long
+		let mut buffer = vec![0; 8192];
+		loop {
+			let n = match kmsg.read(&mut buffer) {
+				Ok(n) => n,
+				// records were overwritten before we got to them; reading on continues from the oldest one left
+				Err(e) if e.raw_os_error() == Some(libc::EPIPE) => continue,
+				Err(e) => {
+					warn!("Stopped reading /dev/kmsg: {e}");
+					return;
+				}
+			};
+			let record = String::from_utf8_lossy(&buffer[..n]);
+			if let Some(message) = parse_kmsg_record(&record)
+				&& tx.blocking_send(message.to_owned()).is_err()
+			{
+				return;
+			}
+		}
+	})?;
+	Ok(())
+}
+
+fn follow_journal(tx: mpsc::Sender<String>) -> Result<()> {
+	let mut journalctl = Command::new("journalctl")
+		.args(["--dmesg", "--follow", "--lines=0", "--output=export"])
+		.stdout(Stdio::piped())
+		.kill_on_drop(true)
+		.spawn()?;
+	let stdout = journalctl.stdout.take().ok_or_else(|| eyre!("journalctl has no stdout"))?;
+
+	tokio::spawn(async move {
+		let mut reader = BufReader::new(stdout);
+		loop {
+			match read_export_entry(&mut reader).await {
+				Ok(Some(entry)) => {
+					let Some(message) = entry.get("MESSAGE") else { continue };
+					if tx.send(String::from_utf8_lossy(message).into_owned()).await.is_err() {
+						break;
+					}
+				}
+				Ok(None) => {
+					warn!("journalctl exited, no longer following the kernel log");
+					break;
+				}
+				Err(e) => {
+					warn!("Stopped reading journalctl output: {e}");
+					break;
+				}
+			}
+		}
+		let _ = journalctl.kill().await;
+	});
+	Ok(())
+}
+
+/// Reads one entry of the [journal export format](https://systemd.io/JOURNAL_EXPORT_FORMATS/); None at the end of the stream.
+///
+/// Fields are `KEY=value` lines, except for values that aren't plain text, which come as `KEY\n`, a little-endian u64 length, the raw bytes and `\n`. An empty line ends the entry.
+pub async fn read_export_entry<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<HashMap<String, Vec<u8>>>> {
+	let mut entry = HashMap::new();
+	let mut line = Vec::new();
+	loop {
+		line.clear();
+		if reader.read_until(b'\n', &mut line).await? == 0 {
+			return Ok((!entry.is_empty()).then_some(entry));
+		}
+		if line.last() == Some(&b'\n') {
+			line.pop();
+		}
+		if line.is_empty() {
+			if entry.is_empty() {
+				continue;
+			}
+			return Ok(Some(entry));
+		}
+
+		match line.iter().position(|&b| b == b'=') {
+			Some(eq) => {
+				entry.insert(String::from_utf8_lossy(&line[..eq]).into_owned(), line[eq + 1..].to_vec());
+			}
+			None => {
+				let len = reader.read_u64_le().await?;
+				let mut value = vec![0; len as usize];
+				reader.read_exact(&mut value).await?;
+				// trailing newline
+				reader.read_u8().await?;
+				entry.insert(String::from_utf8_lossy(&line).into_owned(), value);
+			}
+		}
+	}
+}
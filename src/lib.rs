@@ -0,0 +1,14 @@
+pub mod alerts;
+pub mod checks;
+pub mod config;
+pub mod fanotify;
+pub mod index;
+pub mod kernel_log;
+pub mod mounts;
+pub mod notify;
+pub mod probe;
+pub mod proc;
+pub mod state;
+pub mod systemd;
+pub mod throttle;
+pub mod walk;
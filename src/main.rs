@@ -1,17 +1,30 @@
-mod config;
-
 use std::{
-	fs,
 	path::Path,
+	sync::Arc,
 	time::{Duration, SystemTime},
 };
 
 use clap::{Parser, Subcommand};
-use color_eyre::eyre::{Result, eyre};
-use config::{AppConfig, SettingsFlags};
-use reqwest::Client;
-use tracing::{error, info};
-use v_utils::{utils::InfoSize, xdg_state_file};
+use color_eyre::eyre::Result;
+use server_upkeep::{
+	alerts::Alerter,
+	checks::{
+		dirs::watch_dir,
+		disk::{MountTasks, check_disk_usage},
+		endpoints::watch_endpoints,
+		kernel::watch_kernel_log,
+		load::watch_load,
+		processes::watch_processes,
+		systemd::watch_systemd,
+	},
+	config::{AppConfig, SettingsFlags, ThrottleConfig},
+	notify::Notifiers,
+	state::StateStore,
+	throttle::{RateLimiter, spawn_throttled},
+	walk::walk_pool,
+};
+use tracing::error;
+use v_utils::xdg_state_file;
 
 #[derive(Parser)]
 #[command(author, version = concat!(env!("CARGO_PKG_VERSION"), " (", env!("GIT_HASH"), ")"), about, long_about = None)]
@@ -24,7 +37,7 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-	/// Monitor ~/.local/state directory size and alert if over threshold
+	/// Monitor disk usage, sizes of watched directories, memory, load, systemd units, required processes and endpoints; alert when over thresholds, on kernel errors, or when something is down
 	Monitor,
 	/// Clean files in /tmp that are older than 1 hour
 	//TODO!!!!: at least extend to require provision of [Timeframe](v_utils::trades::Timeframe)
@@ -39,143 +52,75 @@ enum Commands {
 async fn main() -> Result<()> {
 	v_utils::clientside!();
 	let cli = Cli::parse();
-	let config = AppConfig::try_build(cli.settings)?;
+	let mut config = AppConfig::try_build(cli.settings)?;
+	config.migrate_legacy()?;
+	config.validate()?;
 
 	match cli.command {
 		Commands::Monitor => monitor(config).await?,
-		Commands::Tempfiles { daemon } => tempfiles(daemon).await?,
+		Commands::Tempfiles { daemon } => tempfiles(daemon, &config.throttle).await?,
 	}
 
 	Ok(())
 }
 
-const DISK_USAGE_THRESHOLDS: &[u8] = &[50, 60, 70, 80, 90, 95];
-const DISK_USAGE_RESET_THRESHOLD: u8 = 45;
-const MONITOR_INTERVAL: Duration = Duration::from_secs(60 * 60); // 1 hour
-
 async fn monitor(config: AppConfig) -> Result<()> {
-	let state_dir = dirs::state_dir().ok_or_else(|| eyre!("Could not determine state directory"))?;
+	let mut store = StateStore::load(xdg_state_file!("alerts.json"))?;
+	store.import_last_pct_used(&xdg_state_file!("last_pct_used"), &config.monitor.disk_severity)?;
+	let alerter = Arc::new(Alerter::new(Notifiers::from_config(&config.alerts)?, store));
+	let walk_pool = walk_pool(config.monitor.walk_threads, &config.throttle)?;
 
-	loop {
-		// Check ~/.local/state directory size
-		match get_dir_size(&state_dir) {
-			Ok(size_bytes) => {
-				let size = InfoSize::from_parts(size_bytes, v_utils::utils::InfoSizeUnit::Byte);
-				let max_size = config.monitor.max_size;
-				info!("~/.local/state size: {size} (threshold: {max_size})");
-
-				if size > max_size {
-					let message = format!("⚠️ Server Alert: ~/.local/state is {size}, exceeds threshold of {max_size}");
-					if let Err(e) = send_telegram_alert(&config.telegram, &message).await {
-						error!("Failed to send state dir alert: {e}");
-					} else {
-						info!("State dir alert sent to Telegram");
-					}
-				}
-			}
-			Err(e) => error!("Failed to get state directory size: {e}"),
-		}
+	// Memory, load and CPU/IO pressure are sampled more often than the rest, to catch memory running out and tell sustained pressure from spikes
+	tokio::spawn(watch_load(config.monitor.clone(), Arc::clone(&alerter)));
 
-		// Check disk usage percentage of /
-		if let Err(e) = check_disk_usage(&config).await {
-			error!("Failed to check disk usage: {e}");
-		}
-
-		tokio::time::sleep(MONITOR_INTERVAL).await;
+	if config.monitor.kernel_log {
+		tokio::spawn(watch_kernel_log(config.monitor.clone(), Arc::clone(&alerter)));
 	}
-}
-
-async fn check_disk_usage(config: &AppConfig) -> Result<()> {
-	let statvfs = nix::sys::statvfs::statvfs("/")?;
-	let total_blocks = statvfs.blocks();
-	let available_blocks = statvfs.blocks_available();
-	let used_blocks = total_blocks - available_blocks;
-	let usage_pct = (used_blocks as f64 / total_blocks as f64 * 100.0) as u8;
-
-	info!("/ disk usage: {usage_pct}%");
 
-	let state_file = xdg_state_file!("last_pct_used");
-
-	// If usage dropped below reset threshold, delete state file
-	if usage_pct < DISK_USAGE_RESET_THRESHOLD {
-		if state_file.exists() {
-			fs::remove_file(&state_file)?;
-			info!("Disk usage below {DISK_USAGE_RESET_THRESHOLD}%, cleared alert state");
-		}
-		return Ok(());
+	if config.monitor.systemd {
+		tokio::spawn(watch_systemd(config.monitor.clone(), Arc::clone(&alerter)));
 	}
 
-	// Find the highest threshold that current usage exceeds (minimum is 50%)
-	let current_threshold = DISK_USAGE_THRESHOLDS.iter().rev().find(|&&t| usage_pct >= t).copied();
-
-	let Some(threshold) = current_threshold else {
-		// usage_pct is between DISK_USAGE_RESET_THRESHOLD and 50%, no alert needed
-		return Ok(());
-	};
-
-	// Check last alerted threshold
-	let last_alerted: Option<u8> = if state_file.exists() { fs::read_to_string(&state_file)?.trim().parse().ok() } else { None };
-
-	// Only alert if we crossed a new threshold
-	if last_alerted.is_none() || threshold > last_alerted.unwrap() {
-		let message = format!("⚠️ Server Alert: / disk usage at {usage_pct}% (crossed {threshold}% threshold)");
-		match send_telegram_alert(&config.telegram, &message).await {
-			Ok(()) => {
-				fs::write(&state_file, threshold.to_string())?;
-				info!("Disk usage alert sent for {threshold}% threshold");
-			}
-			Err(e) => error!("Failed to send disk usage alert: {e}"),
-		}
+	if !config.monitor.processes.is_empty() {
+		tokio::spawn(watch_processes(config.monitor.clone(), Arc::clone(&alerter)));
 	}
 
-	Ok(())
-}
-
-fn get_dir_size(path: &Path) -> Result<u64> {
-	let mut total_size = 0u64;
-
-	if path.is_dir() {
-		for entry in std::fs::read_dir(path)? {
-			let entry = entry?;
-			let path = entry.path();
-			if path.is_dir() {
-				total_size += get_dir_size(&path)?;
-			} else {
-				total_size += entry.metadata()?.len();
-			}
-		}
+	if !config.monitor.endpoints.is_empty() {
+		tokio::spawn(watch_endpoints(config.monitor.clone(), Arc::clone(&alerter)));
 	}
 
-	Ok(total_size)
-}
-
-async fn send_telegram_alert(config: &config::TelegramConfig, message: &str) -> Result<()> {
-	let client = Client::new();
-	let url = format!("https://api.telegram.org/bot{}/sendMessage", config.bot_token);
-
-	let params = [("chat_id", config.alerts_chat.as_str()), ("text", message)];
+	// Each watched directory is on its own schedule
+	for dir in config.monitor.watched_dirs.clone() {
+		tokio::spawn(watch_dir(config.monitor.clone(), config.throttle.clone(), Arc::clone(&walk_pool), Arc::clone(&alerter), dir));
+	}
 
-	let response = client.post(&url).form(&params).send().await?;
+	let mut mount_tasks = MountTasks::default();
+	loop {
+		// Check disk usage percentage of every mounted filesystem
+		if let Err(e) = check_disk_usage(&config.monitor, &config.throttle, &walk_pool, &alerter, &mut mount_tasks).await {
+			error!("Failed to check disk usage: {e}");
+		}
 
-	if !response.status().is_success() {
-		let error_text = response.text().await?;
-		return Err(eyre!("Failed to send Telegram message: {error_text}"));
+		tokio::time::sleep(config.monitor.interval).await;
 	}
-
-	Ok(())
 }
 
-async fn tempfiles(daemon: bool) -> Result<()> {
+async fn tempfiles(daemon: bool, throttle: &ThrottleConfig) -> Result<()> {
 	loop {
-		let tmp_dir = Path::new("/tmp");
-		let max_age = Duration::from_secs(60 * 60); // 1 hour
-		let now = SystemTime::now();
+		let limiter = RateLimiter::new(throttle.entries_per_sec);
+		let (deleted_count, deleted_bytes, error_count) = spawn_throttled(throttle, move || {
+			let tmp_dir = Path::new("/tmp");
+			let max_age = Duration::from_secs(60 * 60); // 1 hour
+			let now = SystemTime::now();
 
-		let mut deleted_count = 0u64;
-		let mut deleted_bytes = 0u64;
-		let mut error_count = 0u64;
+			let mut deleted_count = 0u64;
+			let mut deleted_bytes = 0u64;
+			let mut error_count = 0u64;
 
-		clean_old_files(tmp_dir, now, max_age, &mut deleted_count, &mut deleted_bytes, &mut error_count);
+			clean_old_files(tmp_dir, now, max_age, &limiter, &mut deleted_count, &mut deleted_bytes, &mut error_count);
+			(deleted_count, deleted_bytes, error_count)
+		})
+		.await?;
 
 		println!(
 			"Cleaned /tmp: deleted {} files ({:.2} MB), {} errors",
@@ -194,17 +139,18 @@ async fn tempfiles(daemon: bool) -> Result<()> {
 	Ok(())
 }
 
-fn clean_old_files(dir: &Path, now: SystemTime, max_age: Duration, deleted_count: &mut u64, deleted_bytes: &mut u64, error_count: &mut u64) {
+fn clean_old_files(dir: &Path, now: SystemTime, max_age: Duration, limiter: &RateLimiter, deleted_count: &mut u64, deleted_bytes: &mut u64, error_count: &mut u64) {
 	let entries = match std::fs::read_dir(dir) {
 		Ok(e) => e,
 		Err(_) => return,
 	};
 
 	for entry in entries.flatten() {
+		limiter.entry();
 		let path = entry.path();
 
 		if path.is_dir() {
-			clean_old_files(&path, now, max_age, deleted_count, deleted_bytes, error_count);
+			clean_old_files(&path, now, max_age, limiter, deleted_count, deleted_bytes, error_count);
 			// Try to remove the directory if it's empty and old enough
 			if let Ok(meta) = std::fs::metadata(&path) {
 				if let Ok(modified) = meta.modified() {
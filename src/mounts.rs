@@ -0,0 +1,85 @@
+//! Mounted filesystems, as listed in /proc/self/mountinfo.
+use std::{fs, path::PathBuf};
+
+use color_eyre::eyre::Result;
+
+/// Filesystems that don't hold data worth watching: kernel interfaces, in-memory scratch space, read-only images that are always full
+pub const PSEUDO_FS_TYPES: &[&str] = &[
+	"autofs", "binfmt_misc", "bpf", "cgroup", "cgroup2", "configfs", "debugfs", "devpts", "devtmpfs", "efivarfs", "fusectl", "hugetlbfs", "mqueue", "nsfs", "overlay", "proc", "pstore",
+	"ramfs", "rpc_pipefs", "securityfs", "selinuxfs", "squashfs", "sysfs", "tmpfs", "tracefs",
+];
+
+/// Filesystems served over the network, where statvfs can hang for as long as the server is unreachable. FUSE filesystems (`fuse.*`) are mostly of this kind too.
+pub const NETWORK_FS_TYPES: &[&str] = &[
+	"9p", "afs", "ceph", "cifs", "davfs", "fuse", "glusterfs", "lustre", "ncpfs", "nfs", "nfs4", "smb3", "smbfs", "sshfs",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mount {
+	/// "major:minor" of the device
+	pub device: String,
+	/// Path within the filesystem that is mounted; anything but "/" means a bind mount or subvolume
+	pub root: String,
+	pub mount_point: PathBuf,
+	pub fs_type: String,
+	pub source: String,
+}
+
+impl Mount {
+	pub fn is_pseudo(&self) -> bool {
+		PSEUDO_FS_TYPES.contains(&self.fs_type.as_str())
+	}
+
+	pub fn is_network(&self) -> bool {
+		NETWORK_FS_TYPES.contains(&self.fs_type.as_str()) || self.fs_type.starts_with("fuse.")
+	}
+}
+
+pub fn read_mounts() -> Result<Vec<Mount>> {
+	Ok(parse_mountinfo(&fs::read_to_string("/proc/self/mountinfo")?))
+}
+
+/// Lines look like `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`, with a variable number of optional fields before the `-`. Malformed lines are skipped.
+pub fn parse_mountinfo(s: &str) -> Vec<Mount> {
+	s.lines()
+		.filter_map(|line| {
+			let (before, after) = line.split_once(" - ")?;
+			let mut before = before.split(' ');
+			let device = before.nth(2)?;
+			let root = before.next()?;
+			let mount_point = before.next()?;
+			let mut after = after.split(' ');
+			let fs_type = after.next()?;
+			let source = after.next()?;
+			Some(Mount {
+				device: device.to_owned(),
+				root: unescape(root),
+				mount_point: PathBuf::from(unescape(mount_point)),
+				fs_type: fs_type.to_owned(),
+				source: unescape(source),
+			})
+		})
+		.collect()
+}
+
+/// mountinfo octal-escapes space, tab, newline and backslash (`\040` etc.)
+fn unescape(s: &str) -> String {
+	let mut out = String::with_capacity(s.len());
+	let mut rest = s;
+	while let Some(i) = rest.find('\\') {
+		out.push_str(&rest[..i]);
+		let code = rest.get(i + 1..i + 4).and_then(|oct| u8::from_str_radix(oct, 8).ok());
+		match code {
+			Some(c) => {
+				out.push(c as char);
+				rest = &rest[i + 4..];
+			}
+			None => {
+				out.push('\\');
+				rest = &rest[i + 1..];
+			}
+		}
+	}
+	out.push_str(rest);
+	out
+}
@@ -0,0 +1,60 @@
+use color_eyre::eyre::Result;
+use lettre::{
+	AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+	message::{Mailbox, header::ContentType},
+	transport::smtp::authentication::Credentials,
+};
+
+use super::{Alert, Notifier};
+use crate::config::{EmailConfig, SmtpTls};
+
+#[derive(Clone, Debug)]
+pub struct EmailNotifier {
+	transport: AsyncSmtpTransport<Tokio1Executor>,
+	from: Mailbox,
+	to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+	pub fn new(config: &EmailConfig) -> Result<Self> {
+		let mut builder = match config.tls {
+			SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+			SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+			SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+		};
+		if let Some(port) = config.port {
+			builder = builder.port(port);
+		}
+		if let (Some(username), Some(password)) = (&config.username, &config.password) {
+			builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+		}
+
+		let from = config.from.parse()?;
+		let to = config.to.iter().map(|addr| addr.parse()).collect::<Result<Vec<Mailbox>, _>>()?;
+
+		Ok(Self {
+			transport: builder.build(),
+			from,
+			to,
+		})
+	}
+}
+
+impl Notifier for EmailNotifier {
+	fn kind(&self) -> &'static str {
+		"email"
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let mut message = Message::builder()
+			.from(self.from.clone())
+			.subject(format!("[{}] {}: {}", alert.severity, alert.host, alert.check));
+		for to in &self.to {
+			message = message.to(to.clone());
+		}
+		let message = message.header(ContentType::TEXT_PLAIN).body(alert.to_string())?;
+
+		self.transport.send(message).await?;
+		Ok(())
+	}
+}
@@ -0,0 +1,49 @@
+use color_eyre::eyre::{Result, eyre};
+use derive_new::new;
+use reqwest::Client;
+use serde_json::json;
+
+use super::{Alert, Notifier, Severity, push_severity, title};
+use crate::config::GotifyConfig;
+
+#[derive(Clone, Debug, new)]
+pub struct GotifyNotifier {
+	client: Client,
+	config: GotifyConfig,
+}
+
+impl GotifyNotifier {
+	/// Gotify priorities go from 0 to 10; Android clients stay silent below 4 and pop up from 8
+	fn priority(&self, severity: Severity) -> u8 {
+		self.config.priorities.get(&severity).copied().unwrap_or(match severity {
+			Severity::Info => 2,
+			Severity::Warning => 5,
+			Severity::Critical => 8,
+			Severity::Emergency => 10,
+		})
+	}
+}
+
+impl Notifier for GotifyNotifier {
+	fn kind(&self) -> &'static str {
+		"gotify"
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let url = format!("{}/message", self.config.server.trim_end_matches('/'));
+		let body = json!({
+			"title": title(alert),
+			"message": alert.message,
+			"priority": self.priority(push_severity(alert)),
+		});
+
+		let response = self.client.post(&url).header("X-Gotify-Key", &self.config.app_token).json(&body).send().await?;
+
+		if !response.status().is_success() {
+			let error_text = response.text().await?;
+			return Err(eyre!("Failed to send Gotify message: {error_text}"));
+		}
+
+		Ok(())
+	}
+}
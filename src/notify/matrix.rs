@@ -0,0 +1,81 @@
+use std::{
+	sync::atomic::{AtomicU64, Ordering},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{Result, eyre};
+use derive_new::new;
+use reqwest::{Client, Url};
+use serde_json::json;
+
+use super::{Alert, Notifier};
+use crate::config::MatrixConfig;
+
+#[derive(Clone, Debug, new)]
+pub struct MatrixNotifier {
+	client: Client,
+	config: MatrixConfig,
+}
+
+impl MatrixNotifier {
+	fn send_url(&self) -> Result<Url> {
+		let mut url = Url::parse(&self.config.homeserver)?;
+		url.path_segments_mut()
+			.map_err(|_| eyre!("Homeserver URL can't be a base: {}", self.config.homeserver))?
+			.pop_if_empty()
+			.extend(["_matrix", "client", "v3", "rooms", &self.config.room_id, "send", "m.room.message", &txn_id()]);
+		Ok(url)
+	}
+}
+
+impl Notifier for MatrixNotifier {
+	fn kind(&self) -> &'static str {
+		"matrix"
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let mut content = json!({
+			"msgtype": "m.text",
+			"body": alert.to_string(),
+		});
+		if self.config.html {
+			content["format"] = "org.matrix.custom.html".into();
+			content["formatted_body"] = format_html(alert).into();
+		}
+
+		let response = self.client.put(self.send_url()?).bearer_auth(&self.config.access_token).json(&content).send().await?;
+
+		if !response.status().is_success() {
+			let error_text = response.text().await?;
+			return Err(eyre!("Failed to send Matrix message: {error_text}"));
+		}
+
+		Ok(())
+	}
+}
+
+fn format_html(alert: &Alert) -> String {
+	format!(
+		"<p><b>{}</b> on <code>{}</code> (<code>{}</code>)</p><p>{}</p><p>value: {}, threshold: {}</p>",
+		match alert.resolved {
+			true => "RESOLVED".to_owned(),
+			false => alert.severity.to_string().to_uppercase(),
+		},
+		html_escape(&alert.host),
+		html_escape(&alert.check),
+		html_escape(&alert.message),
+		html_escape(&alert.value),
+		html_escape(&alert.threshold),
+	)
+}
+
+fn html_escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Transaction IDs must be unique per access token, otherwise the homeserver deduplicates the event.
+fn txn_id() -> String {
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+	format!("server_upkeep.{now}.{}", COUNTER.fetch_add(1, Ordering::Relaxed))
+}
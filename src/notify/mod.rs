@@ -0,0 +1,256 @@
+//! Alert delivery. Checks produce an [Alert], [Notifiers] fans it out to every configured sink.
+mod email;
+mod gotify;
+mod matrix;
+mod ntfy;
+pub mod routing;
+mod telegram;
+mod webhook;
+
+use std::{collections::BTreeMap, fmt, future::Future, ops::RangeInclusive};
+
+use chrono::Local;
+use color_eyre::eyre::{Result, bail};
+use derive_new::new;
+pub use email::EmailNotifier;
+use futures::future::join_all;
+pub use gotify::GotifyNotifier;
+pub use matrix::MatrixNotifier;
+pub use ntfy::NtfyNotifier;
+use reqwest::Client;
+use routing::Route;
+use serde::{Deserialize, Serialize};
+pub use telegram::TelegramNotifier;
+use tracing::{error, info, warn};
+pub use webhook::{WebhookNotifier, render_template};
+
+use crate::config::{AlertsConfig, SinkKind};
+
+#[derive(Clone, Debug, new)]
+pub struct Alert {
+	#[new(value = "hostname()")]
+	pub host: String,
+	/// Identifier of the check that produced this alert (e.g. "disk:/", "kernel:oom")
+	#[new(into)]
+	pub check: String,
+	pub severity: Severity,
+	/// Observed value, human-readable (e.g. "91%", "12GB")
+	#[new(into)]
+	pub value: String,
+	/// Threshold that `value` was compared against, in the same form
+	#[new(into)]
+	pub threshold: String,
+	#[new(into)]
+	pub message: String,
+	/// Announces that the condition behind an earlier alert is gone
+	#[new(value = "false")]
+	pub resolved: bool,
+}
+
+impl Alert {
+	pub fn resolved(mut self) -> Self {
+		self.resolved = true;
+		self
+	}
+}
+
+impl fmt::Display for Alert {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.resolved {
+			true => write!(f, "✅ [RESOLVED] {}: {}", self.host, self.message),
+			false => write!(f, "{} [{}] {}: {}", self.severity.emoji(), self.severity.to_string().to_uppercase(), self.host, self.message),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+	Info,
+	#[default]
+	Warning,
+	Critical,
+	Emergency,
+}
+
+impl Severity {
+	pub fn emoji(&self) -> &'static str {
+		match self {
+			Self::Info => "ℹ️",
+			Self::Warning => "⚠️",
+			Self::Critical => "🚨",
+			Self::Emergency => "🔥",
+		}
+	}
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = match self {
+			Self::Info => "info",
+			Self::Warning => "warning",
+			Self::Critical => "critical",
+			Self::Emergency => "emergency",
+		};
+		f.write_str(s)
+	}
+}
+
+pub trait Notifier {
+	/// Name of the backend, for logs
+	fn kind(&self) -> &'static str;
+	fn send(&self, alert: &Alert) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// One configured destination for alerts
+#[derive(Clone, Debug)]
+pub enum Sink {
+	Telegram(TelegramNotifier),
+	Email(EmailNotifier),
+	Webhook(WebhookNotifier),
+	Matrix(MatrixNotifier),
+	Ntfy(NtfyNotifier),
+	Gotify(GotifyNotifier),
+}
+
+impl Sink {
+	pub fn from_config(config: &SinkKind, client: &Client) -> Result<Self> {
+		Ok(match config {
+			SinkKind::Telegram(c) => Self::Telegram(TelegramNotifier::new(client.clone(), c.clone())),
+			SinkKind::Email(c) => Self::Email(EmailNotifier::new(c)?),
+			SinkKind::Webhook(c) => Self::Webhook(WebhookNotifier::new(client.clone(), c)?),
+			SinkKind::Matrix(c) => Self::Matrix(MatrixNotifier::new(client.clone(), c.clone())),
+			SinkKind::Ntfy(c) => {
+				validate_priorities("ntfy", &c.priorities, 1..=5)?;
+				Self::Ntfy(NtfyNotifier::new(client.clone(), c.clone()))
+			}
+			SinkKind::Gotify(c) => {
+				validate_priorities("gotify", &c.priorities, 0..=10)?;
+				Self::Gotify(GotifyNotifier::new(client.clone(), c.clone()))
+			}
+		})
+	}
+}
+
+/// Priorities the backend doesn't accept would otherwise only show up as failed sends, when an alert is already due
+fn validate_priorities(kind: &str, priorities: &BTreeMap<Severity, u8>, range: RangeInclusive<u8>) -> Result<()> {
+	if let Some((severity, priority)) = priorities.iter().find(|(_, p)| !range.contains(p)) {
+		bail!("{kind}: priority {priority} for {severity} is out of the {}-{} range", range.start(), range.end());
+	}
+	Ok(())
+}
+
+impl Notifier for Sink {
+	fn kind(&self) -> &'static str {
+		match self {
+			Self::Telegram(n) => n.kind(),
+			Self::Email(n) => n.kind(),
+			Self::Webhook(n) => n.kind(),
+			Self::Matrix(n) => n.kind(),
+			Self::Ntfy(n) => n.kind(),
+			Self::Gotify(n) => n.kind(),
+		}
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		match self {
+			Self::Telegram(n) => n.send(alert).await,
+			Self::Email(n) => n.send(alert).await,
+			Self::Webhook(n) => n.send(alert).await,
+			Self::Matrix(n) => n.send(alert).await,
+			Self::Ntfy(n) => n.send(alert).await,
+			Self::Gotify(n) => n.send(alert).await,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Notifiers {
+	sinks: Vec<Sink>,
+	/// Parallel to `sinks`
+	sink_names: Vec<String>,
+	routes: Vec<Route>,
+	host_tags: Vec<String>,
+}
+
+impl Notifiers {
+	pub fn from_config(config: &AlertsConfig) -> Result<Self> {
+		config.validate()?;
+		let client = Client::new();
+		let sinks = config.sinks.iter().map(|c| Sink::from_config(&c.kind, &client)).collect::<Result<Vec<_>>>()?;
+		let sink_names: Vec<String> = config.sinks.iter().map(|c| c.name().to_owned()).collect();
+		let routes = config.routes.iter().map(|r| Route::from_config(r, &sink_names)).collect::<Result<_>>()?;
+
+		Ok(Self {
+			sinks,
+			sink_names,
+			routes,
+			host_tags: config.host_tags.clone(),
+		})
+	}
+
+	/// Indices of sinks `alert` should go to. Without routes that's all of them; otherwise the first matching route decides.
+	fn select(&self, alert: &Alert) -> Vec<usize> {
+		if self.routes.is_empty() {
+			return (0..self.sinks.len()).collect();
+		}
+		let now = Local::now().time();
+		self.routes.iter().find(|r| r.matches(alert, &self.host_tags, now)).map(|r| r.sinks.clone()).unwrap_or_default()
+	}
+
+	/// Sends `alert` to the sinks selected by routing, concurrently.
+	///
+	/// Succeeds if at least one sink delivered it; failures of individual sinks are only logged, so that a single broken channel doesn't cause re-sends through the working ones.
+	pub async fn notify(&self, alert: &Alert) -> Result<()> {
+		match alert.severity {
+			_ if alert.resolved => info!(severity = %alert.severity, check = alert.check, "Resolved: {}", alert.message),
+			Severity::Info => info!(severity = %alert.severity, check = alert.check, "{}", alert.message),
+			Severity::Warning => warn!(severity = %alert.severity, check = alert.check, "{}", alert.message),
+			Severity::Critical | Severity::Emergency => error!(severity = %alert.severity, check = alert.check, "{}", alert.message),
+		}
+
+		if self.sinks.is_empty() {
+			bail!("No alert sinks configured");
+		}
+
+		// config validation requires a catch-all route, but an alert that went nowhere mustn't count as delivered either way
+		let selected = self.select(alert);
+		if selected.is_empty() {
+			bail!("No route matched alert `{}`", alert.check);
+		}
+
+		let results = join_all(selected.iter().map(|&i| self.sinks[i].send(alert))).await;
+		let mut delivered = false;
+		for (&i, result) in selected.iter().zip(results) {
+			match result {
+				Ok(()) => delivered = true,
+				Err(e) => error!(sink = self.sink_names[i], kind = self.sinks[i].kind(), "Failed to deliver `{}` alert: {e}", alert.check),
+			}
+		}
+
+		match delivered {
+			true => Ok(()),
+			false => bail!("Alert `{}` could not be delivered to any sink", alert.check),
+		}
+	}
+}
+
+/// Short summary line, for backends that have a separate title field
+fn title(alert: &Alert) -> String {
+	match alert.resolved {
+		true => format!("Resolved: {}: {}", alert.host, alert.check),
+		false => format!("{}: {}", alert.host, alert.check),
+	}
+}
+
+/// Recoveries are good news, they shouldn't wake anyone up
+fn push_severity(alert: &Alert) -> Severity {
+	match alert.resolved {
+		true => Severity::Info,
+		false => alert.severity,
+	}
+}
+
+fn hostname() -> String {
+	nix::unistd::gethostname().ok().and_then(|h| h.into_string().ok()).unwrap_or_else(|| "unknown".to_owned())
+}
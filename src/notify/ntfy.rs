@@ -0,0 +1,53 @@
+use color_eyre::eyre::{Result, eyre};
+use derive_new::new;
+use reqwest::Client;
+
+use super::{Alert, Notifier, Severity, push_severity, title};
+use crate::config::NtfyConfig;
+
+#[derive(Clone, Debug, new)]
+pub struct NtfyNotifier {
+	client: Client,
+	config: NtfyConfig,
+}
+
+impl NtfyNotifier {
+	/// ntfy priorities go from 1 (min) to 5 (max)
+	fn priority(&self, severity: Severity) -> u8 {
+		self.config.priorities.get(&severity).copied().unwrap_or(match severity {
+			Severity::Info => 2,
+			Severity::Warning => 3,
+			Severity::Critical => 4,
+			Severity::Emergency => 5,
+		})
+	}
+}
+
+impl Notifier for NtfyNotifier {
+	fn kind(&self) -> &'static str {
+		"ntfy"
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let url = format!("{}/{}", self.config.server.trim_end_matches('/'), self.config.topic);
+		let mut request = self
+			.client
+			.post(&url)
+			.header("Title", title(alert))
+			.header("Priority", self.priority(push_severity(alert)).to_string())
+			.header("Tags", if alert.resolved { "white_check_mark".to_owned() } else { alert.severity.to_string() })
+			.body(alert.message.clone());
+		if let Some(token) = &self.config.token {
+			request = request.bearer_auth(token);
+		}
+
+		let response = request.send().await?;
+
+		if !response.status().is_success() {
+			let error_text = response.text().await?;
+			return Err(eyre!("Failed to send ntfy notification: {error_text}"));
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,61 @@
+use chrono::NaiveTime;
+use color_eyre::eyre::{Result, bail, eyre};
+
+use super::{Alert, Severity};
+use crate::config::RouteConfig;
+
+/// [RouteConfig] with sink names resolved to indices into [Notifiers](super::Notifiers)' sinks
+#[derive(Clone, Debug)]
+pub struct Route {
+	checks: Vec<String>,
+	min_severity: Option<Severity>,
+	host_tags: Vec<String>,
+	between: Option<(NaiveTime, NaiveTime)>,
+	pub sinks: Vec<usize>,
+}
+
+impl Route {
+	pub fn from_config(config: &RouteConfig, sink_names: &[String]) -> Result<Self> {
+		let sinks = config
+			.sinks
+			.iter()
+			.map(|name| sink_names.iter().position(|n| n == name).ok_or_else(|| eyre!("Route refers to unknown sink `{name}`")))
+			.collect::<Result<Vec<_>>>()?;
+		if sinks.is_empty() {
+			bail!("Route must deliver to at least one sink");
+		}
+
+		let between = config.between.as_deref().map(parse_time_window).transpose()?;
+
+		Ok(Self {
+			checks: config.checks.clone(),
+			min_severity: config.min_severity,
+			host_tags: config.host_tags.clone(),
+			between,
+			sinks,
+		})
+	}
+
+	pub fn matches(&self, alert: &Alert, host_tags: &[String], now: NaiveTime) -> bool {
+		let check_matches = self.checks.is_empty()
+			|| self.checks.iter().any(|pattern| match pattern.strip_suffix('*') {
+				Some(prefix) => alert.check.starts_with(prefix),
+				None => alert.check == *pattern,
+			});
+		let severity_matches = self.min_severity.is_none_or(|min| alert.severity >= min);
+		let tags_match = self.host_tags.iter().all(|t| host_tags.contains(t));
+		let time_matches = self.between.is_none_or(|(from, to)| match from <= to {
+			true => from <= now && now < to,
+			// wraps around midnight
+			false => now >= from || now < to,
+		});
+
+		check_matches && severity_matches && tags_match && time_matches
+	}
+}
+
+/// "HH:MM-HH:MM"
+pub fn parse_time_window(s: &str) -> Result<(NaiveTime, NaiveTime)> {
+	let (from, to) = s.split_once('-').ok_or_else(|| eyre!("Expected time window as \"HH:MM-HH:MM\", got \"{s}\""))?;
+	Ok((NaiveTime::parse_from_str(from.trim(), "%H:%M")?, NaiveTime::parse_from_str(to.trim(), "%H:%M")?))
+}
@@ -0,0 +1,34 @@
+use color_eyre::eyre::{Result, eyre};
+use derive_new::new;
+use reqwest::Client;
+
+use super::{Alert, Notifier};
+use crate::config::TelegramConfig;
+
+#[derive(Clone, Debug, new)]
+pub struct TelegramNotifier {
+	client: Client,
+	config: TelegramConfig,
+}
+
+impl Notifier for TelegramNotifier {
+	fn kind(&self) -> &'static str {
+		"telegram"
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let url = format!("https://api.telegram.org/bot{}/sendMessage", self.config.bot_token);
+
+		let text = alert.to_string();
+		let params = [("chat_id", self.config.alerts_chat.as_str()), ("text", text.as_str())];
+
+		let response = self.client.post(&url).form(&params).send().await?;
+
+		if !response.status().is_success() {
+			let error_text = response.text().await?;
+			return Err(eyre!("Failed to send Telegram message: {error_text}"));
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,97 @@
+use color_eyre::eyre::{Result, eyre};
+use reqwest::{
+	Client, Method,
+	header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
+};
+
+use super::{Alert, Notifier};
+use crate::config::WebhookConfig;
+
+#[derive(Clone, Debug)]
+pub struct WebhookNotifier {
+	client: Client,
+	url: String,
+	method: Method,
+	headers: HeaderMap,
+	body: String,
+}
+
+impl WebhookNotifier {
+	pub fn new(client: Client, config: &WebhookConfig) -> Result<Self> {
+		let method = Method::from_bytes(config.method.to_ascii_uppercase().as_bytes())?;
+
+		let mut headers = HeaderMap::new();
+		headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+		for (name, value) in &config.headers {
+			headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+		}
+
+		Ok(Self {
+			client,
+			url: config.url.clone(),
+			method,
+			headers,
+			body: config.body.clone(),
+		})
+	}
+}
+
+impl Notifier for WebhookNotifier {
+	fn kind(&self) -> &'static str {
+		"webhook"
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let body = render_template(&self.body, alert);
+		let response = self.client.request(self.method.clone(), &self.url).headers(self.headers.clone()).body(body).send().await?;
+
+		if !response.status().is_success() {
+			let status = response.status();
+			let error_text = response.text().await?;
+			return Err(eyre!("Webhook responded with {status}: {error_text}"));
+		}
+
+		Ok(())
+	}
+}
+
+/// Substitutes `{{var}}` placeholders. Values are JSON-escaped, so they're safe to place inside string literals of the template.
+///
+/// Done in a single pass, so placeholders showing up in the values themselves (e.g. in a message) are left as they are. Unknown ones are kept verbatim.
+pub fn render_template(template: &str, alert: &Alert) -> String {
+	let severity = alert.severity.to_string();
+	let status = if alert.resolved { "resolved" } else { "firing" };
+	let vars = [
+		("status", status),
+		("host", alert.host.as_str()),
+		("check", alert.check.as_str()),
+		("severity", severity.as_str()),
+		("value", alert.value.as_str()),
+		("threshold", alert.threshold.as_str()),
+		("message", alert.message.as_str()),
+	];
+
+	let mut rendered = String::with_capacity(template.len());
+	let mut rest = template;
+	while let Some(start) = rest.find("{{") {
+		rendered.push_str(&rest[..start]);
+		let after = &rest[start + 2..];
+		match after.find("}}").and_then(|end| Some((vars.iter().find(|(name, _)| *name == &after[..end])?, end))) {
+			Some(((_, value), end)) => {
+				rendered.push_str(&json_escape(value));
+				rest = &after[end + 2..];
+			}
+			None => {
+				rendered.push_str("{{");
+				rest = after;
+			}
+		}
+	}
+	rendered.push_str(rest);
+	rendered
+}
+
+fn json_escape(s: &str) -> String {
+	let quoted = serde_json::Value::from(s).to_string();
+	quoted[1..quoted.len() - 1].to_owned()
+}
@@ -0,0 +1,56 @@
+//! Health probes of network endpoints: plain TCP connects and HTTP requests.
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{Result, bail, eyre};
+use regex::Regex;
+use reqwest::{Client, redirect};
+use tokio::net::TcpStream;
+
+/// Client for running [Probe]s with. Redirects aren't followed, as where an endpoint sends us (e.g. a login page) is its answer, not the service's health;
+/// and the proxy environment variables are ignored, as they're meant for reaching the outside, not the local services probed here.
+pub fn client() -> Result<Client> {
+	Ok(Client::builder().redirect(redirect::Policy::none()).no_proxy().build()?)
+}
+
+#[derive(Clone, Debug)]
+pub enum Probe {
+	/// Connecting to `host:port` is all it takes
+	Tcp(String),
+	/// GET `url`, expecting `status` (any 2xx if None) and a body matching `body`
+	Http { url: String, status: Option<u16>, body: Option<Regex> },
+}
+
+impl Probe {
+	/// How long the endpoint took to pass the probe; errors say why it didn't
+	pub async fn run(&self, client: &Client, timeout: Duration) -> Result<Duration> {
+		let started = Instant::now();
+		match tokio::time::timeout(timeout, self.check(client)).await {
+			Ok(result) => result.map(|()| started.elapsed()),
+			Err(_) => bail!("no response within {}ms", timeout.as_millis()),
+		}
+	}
+
+	async fn check(&self, client: &Client) -> Result<()> {
+		match self {
+			Self::Tcp(addr) => {
+				TcpStream::connect(addr).await.map_err(|e| eyre!("can't connect to {addr}: {e}"))?;
+			}
+			Self::Http { url, status, body } => {
+				let response = client.get(url).send().await.map_err(|e| eyre!("request failed: {e}"))?;
+				let got = response.status();
+				match status {
+					Some(expected) if got.as_u16() != *expected => bail!("responded {got}, expected {expected}"),
+					None if !got.is_success() => bail!("responded {got}"),
+					_ => {}
+				}
+				if let Some(body) = body {
+					let text = response.text().await?;
+					if !body.is_match(&text) {
+						bail!("response body doesn't match /{body}/");
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,272 @@
+//! System-wide and per-process resource readings from /proc.
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{Result, eyre};
+use regex::Regex;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemInfo {
+	/// Bytes
+	pub total: u64,
+	/// What can be handed out without swapping, page cache included
+	pub available: u64,
+	pub swap_total: u64,
+	pub swap_free: u64,
+}
+
+impl MemInfo {
+	pub fn used_pct(&self) -> u8 {
+		pct(self.total - self.available.min(self.total), self.total)
+	}
+
+	/// None without swap
+	pub fn swap_used_pct(&self) -> Option<u8> {
+		(self.swap_total > 0).then(|| pct(self.swap_total - self.swap_free.min(self.swap_total), self.swap_total))
+	}
+}
+
+fn pct(part: u64, whole: u64) -> u8 {
+	(part as f64 / whole as f64 * 100.0) as u8
+}
+
+pub fn read_meminfo() -> Result<MemInfo> {
+	parse_meminfo(&fs::read_to_string("/proc/meminfo")?)
+}
+
+/// Lines look like `MemAvailable:    8041276 kB`
+pub fn parse_meminfo(s: &str) -> Result<MemInfo> {
+	let field = |name: &str| {
+		s.lines()
+			.find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+			.and_then(|value| value.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+			.map(|kb| kb * 1024)
+			.ok_or_else(|| eyre!("No {name} in meminfo"))
+	};
+	Ok(MemInfo {
+		total: field("MemTotal")?,
+		available: field("MemAvailable")?,
+		swap_total: field("SwapTotal")?,
+		swap_free: field("SwapFree")?,
+	})
+}
+
+/// Pressure stall information of one resource
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pressure {
+	/// Share of time at least some tasks were stalled on the resource
+	pub some: PressureStats,
+	/// Share of time all non-idle tasks were stalled at once; not reported for cpu by older kernels
+	pub full: Option<PressureStats>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PressureStats {
+	/// %, averaged over the last 10s
+	pub avg10: f64,
+	pub avg60: f64,
+	pub avg300: f64,
+	/// Total stall time, µs
+	pub total: u64,
+}
+
+/// `resource` is one of "cpu", "io", "memory". Fails on kernels built without PSI.
+pub fn read_pressure(resource: &str) -> Result<Pressure> {
+	parse_pressure(&fs::read_to_string(format!("/proc/pressure/{resource}"))?)
+}
+
+/// Lines look like `some avg10=0.00 avg60=1.52 avg300=0.87 total=12345678`
+pub fn parse_pressure(s: &str) -> Result<Pressure> {
+	let stats = |kind: &str| -> Option<PressureStats> {
+		let line = s.lines().find_map(|line| line.strip_prefix(kind)?.strip_prefix(' '))?;
+		let mut stats = PressureStats::default();
+		for pair in line.split_whitespace() {
+			let (key, value) = pair.split_once('=')?;
+			match key {
+				"avg10" => stats.avg10 = value.parse().ok()?,
+				"avg60" => stats.avg60 = value.parse().ok()?,
+				"avg300" => stats.avg300 = value.parse().ok()?,
+				"total" => stats.total = value.parse().ok()?,
+				_ => {}
+			}
+		}
+		Some(stats)
+	};
+	Ok(Pressure {
+		some: stats("some").ok_or_else(|| eyre!("No `some` line in pressure stats"))?,
+		full: stats("full"),
+	})
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadAvg {
+	pub one: f64,
+	pub five: f64,
+	pub fifteen: f64,
+}
+
+pub fn read_loadavg() -> Result<LoadAvg> {
+	parse_loadavg(&fs::read_to_string("/proc/loadavg")?)
+}
+
+/// Looks like `0.52 0.58 0.59 1/1163 12345`
+pub fn parse_loadavg(s: &str) -> Result<LoadAvg> {
+	let mut fields = s.split_whitespace().map(|f| f.parse::<f64>());
+	let mut next = || fields.next().and_then(|f| f.ok()).ok_or_else(|| eyre!("Malformed loadavg: {s:?}"));
+	Ok(LoadAvg {
+		one: next()?,
+		five: next()?,
+		fifteen: next()?,
+	})
+}
+
+/// CPUs currently online on the host, regardless of this process' affinity or cgroup quota, as that's what the load average is relative to
+pub fn online_cpus() -> u32 {
+	// SAFETY: sysconf only reads its argument
+	let n = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+	n.max(1) as u32
+}
+
+/// One process, as of when it was read
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessStat {
+	pub pid: u32,
+	/// Executable name, truncated by the kernel to 15 bytes
+	pub comm: String,
+	/// Program first, then its arguments
+	pub cmdline: Vec<String>,
+	/// Resident memory, bytes
+	pub rss: u64,
+	/// CPU time spent in user and kernel mode so far, in clock ticks
+	pub cpu_ticks: u64,
+	/// Open file descriptors, as counted by [count_fds]; None until then, and where we aren't permitted to look (other users' processes, unless root)
+	pub fds: Option<u64>,
+}
+
+pub fn read_process(pid: u32) -> Result<ProcessStat> {
+	let dir = PathBuf::from(format!("/proc/{pid}"));
+	let mut process = parse_stat(&fs::read_to_string(dir.join("stat"))?)?;
+	process.cmdline = fs::read(dir.join("cmdline"))?
+		.split(|&b| b == 0)
+		.filter(|arg| !arg.is_empty())
+		.map(|arg| String::from_utf8_lossy(arg).into_owned())
+		.collect();
+	Ok(process)
+}
+
+/// A directory listing of its own, so left out of [read_process]
+pub fn count_fds(pid: u32) -> Option<u64> {
+	fs::read_dir(format!("/proc/{pid}/fd")).ok().map(|entries| entries.count() as u64)
+}
+
+/// Looks like `1234 (my proc) S 1 ...`. The name can hold spaces and parens itself, so the fields are counted from the last `)`.
+pub fn parse_stat(s: &str) -> Result<ProcessStat> {
+	let malformed = || eyre!("Malformed stat: {s:?}");
+	let (pid, rest) = s.split_once(" (").ok_or_else(malformed)?;
+	let (comm, rest) = rest.rsplit_once(") ").ok_or_else(malformed)?;
+	let fields: Vec<&str> = rest.split_whitespace().collect();
+	// as numbered in proc(5), where the state right after the name is field 3
+	let field = |n: usize| fields.get(n - 3).and_then(|f| f.parse::<u64>().ok()).ok_or_else(malformed);
+	Ok(ProcessStat {
+		pid: pid.parse()?,
+		comm: comm.to_owned(),
+		rss: field(24)? * page_size(),
+		cpu_ticks: field(14)? + field(15)?,
+		..Default::default()
+	})
+}
+
+/// How a required process is found
+#[derive(Clone, Debug)]
+pub enum ProcessMatcher {
+	/// Executable name, like `pgrep -x`. Also matched against the file name of the program in the command line, as the kernel truncates names to 15 bytes.
+	Name(String),
+	/// Regex on the command line, arguments joined by spaces
+	Cmdline(Regex),
+	/// Whatever process has the PID written in the file
+	Pidfile(PathBuf),
+}
+
+impl ProcessMatcher {
+	/// Currently running processes that match
+	pub fn find(&self) -> Result<Vec<ProcessStat>> {
+		Self::find_all(std::slice::from_ref(self))?.pop().expect("one result per matcher")
+	}
+
+	/// Currently running processes that match, for each of `matchers` in order, listing /proc once for all of them.
+	/// Only fails as a whole when /proc can't be listed; a bad pidfile fails its own matcher.
+	pub fn find_all(matchers: &[Self]) -> Result<Vec<Result<Vec<ProcessStat>>>> {
+		let mut found: Vec<Result<Vec<ProcessStat>>> = matchers
+			.iter()
+			.map(|matcher| match matcher {
+				Self::Pidfile(path) => read_pidfile(path),
+				_ => Ok(Vec::new()),
+			})
+			.collect();
+
+		if matchers.iter().any(|matcher| !matches!(matcher, Self::Pidfile(_))) {
+			for entry in fs::read_dir("/proc")?.flatten() {
+				let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse().ok()) else {
+					continue;
+				};
+				// it may have exited since the listing
+				let Ok(process) = read_process(pid) else {
+					continue;
+				};
+				for (matcher, found) in matchers.iter().zip(&mut found) {
+					if !matches!(matcher, Self::Pidfile(_))
+						&& matcher.matches(&process)
+						&& let Ok(found) = found
+					{
+						found.push(process.clone());
+					}
+				}
+			}
+		}
+
+		// only for what matched, rather than every process on the system
+		for process in found.iter_mut().flatten().flatten() {
+			process.fds = count_fds(process.pid);
+		}
+		Ok(found)
+	}
+
+	pub fn matches(&self, process: &ProcessStat) -> bool {
+		match self {
+			Self::Name(name) => {
+				// kernel threads have no command line, and aren't what anyone means by a process name
+				let program = process.cmdline.first().map(|program| Path::new(program).file_name().unwrap_or_default().to_string_lossy());
+				program.is_some_and(|program| program == *name || process.comm == *name)
+			}
+			Self::Cmdline(regex) => !process.cmdline.is_empty() && regex.is_match(&process.cmdline.join(" ")),
+			// found by the PID alone
+			Self::Pidfile(_) => true,
+		}
+	}
+}
+
+fn read_pidfile(path: &Path) -> Result<Vec<ProcessStat>> {
+	let pid = match fs::read_to_string(path) {
+		Ok(pid) => pid,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+		Err(e) => return Err(e.into()),
+	};
+	let pid: u32 = pid.trim().parse().map_err(|e| eyre!("Bad PID in {}: {e}", path.display()))?;
+	// a stale pidfile points to nothing, or to a zombie that's as good as nothing
+	Ok(read_process(pid).into_iter().filter(|p| !p.cmdline.is_empty()).collect())
+}
+
+/// Clock ticks per second, the unit of CPU times in /proc
+pub fn clock_ticks() -> u64 {
+	// SAFETY: sysconf only reads its argument
+	let n = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+	n.max(1) as u64
+}
+
+pub fn page_size() -> u64 {
+	// SAFETY: sysconf only reads its argument
+	let n = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+	n.max(1) as u64
+}
@@ -0,0 +1,183 @@
+//! Persistent memory of every check's alert lifecycle, keyed by check ID.
+use std::{
+	collections::{BTreeMap, VecDeque},
+	fs,
+	path::{Path, PathBuf},
+	time::{Duration, Instant, SystemTime},
+};
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	config::SeverityTiers,
+	notify::{Alert, Severity},
+};
+
+/// Notifications older than this many per check are forgotten
+const HISTORY_LEN: usize = 100;
+/// Bookkeeping alone (last values, samples, peaks) is persisted at most this often
+const SAVE_EVERY: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct StateStore {
+	#[serde(skip)]
+	path: PathBuf,
+	#[serde(skip)]
+	saved: Option<Instant>,
+	checks: BTreeMap<String, CheckState>,
+}
+
+impl StateStore {
+	/// Starts out empty if nothing was persisted at `path` yet
+	pub fn load(path: PathBuf) -> Result<Self> {
+		let mut store: Self = match path.exists() {
+			true => serde_json::from_str(&fs::read_to_string(&path)?)?,
+			false => Self::default(),
+		};
+		store.path = path;
+		Ok(store)
+	}
+
+	pub fn save(&mut self) -> Result<()> {
+		// write-then-rename, so a crash mid-write can't leave a truncated file behind
+		let tmp = self.path.with_extension("tmp");
+		fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+		fs::rename(&tmp, &self.path)?;
+		self.saved = Some(Instant::now());
+		Ok(())
+	}
+
+	/// [Self::save], unless that was done within the last [SAVE_EVERY]. For changes that are fine to lose on a crash, so that checks observing often don't rewrite the whole file each time.
+	pub fn save_debounced(&mut self) -> Result<()> {
+		match self.saved {
+			Some(saved) if saved.elapsed() < SAVE_EVERY => Ok(()),
+			_ => self.save(),
+		}
+	}
+
+	pub fn get(&self, check: &str) -> Option<&CheckState> {
+		self.checks.get(check)
+	}
+
+	pub fn check_mut(&mut self, check: &str) -> &mut CheckState {
+		self.checks.entry(check.to_owned()).or_default()
+	}
+
+	pub fn checks(&self) -> impl Iterator<Item = (&str, &CheckState)> {
+		self.checks.iter().map(|(check, state)| (check.as_str(), state))
+	}
+
+	/// Takes over the `last_pct_used` file that versions before this store kept, holding the last disk usage threshold (in %) alerted for on /.
+	/// Without it, the first run after upgrading would alert on / all over again. The file is removed once imported.
+	pub fn import_last_pct_used(&mut self, legacy: &Path, severity_tiers: &SeverityTiers) -> Result<()> {
+		if !legacy.exists() {
+			return Ok(());
+		}
+		if let Ok(threshold) = fs::read_to_string(legacy)?.trim().parse::<u8>()
+			&& self.get("disk:/").is_none_or(|state| state.incident.is_none())
+		{
+			let since = fs::metadata(legacy)?.modified().unwrap_or_else(|_| SystemTime::now());
+			let state = self.check_mut("disk:/");
+			state.status = Status::Firing;
+			state.incident = Some(Incident {
+				started: since,
+				severity: severity_tiers.severity(threshold),
+				tier: u32::from(threshold),
+				peak: u64::from(threshold),
+				peak_display: format!("{threshold}%"),
+				last_notified: since,
+			});
+			self.save()?;
+		}
+		fs::remove_file(legacy)?;
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CheckState {
+	pub status: Status,
+	/// Present while `status` is [Status::Firing]
+	pub incident: Option<Incident>,
+	pub last_value: Option<String>,
+	pub last_checked: Option<SystemTime>,
+	pub last_notified: Option<SystemTime>,
+	/// Most recent last
+	pub history: VecDeque<Notification>,
+	/// Recent measurements, for fitting trends. Most recent last.
+	#[serde(default)]
+	pub samples: VecDeque<Sample>,
+}
+
+impl CheckState {
+	/// Appends a measurement, forgetting those older than `window`
+	pub fn record_sample(&mut self, value: u64, window: Duration) {
+		let now = SystemTime::now();
+		self.samples.push_back(Sample { at: now, value });
+		while self.samples.front().is_some_and(|s| now.duration_since(s.at).unwrap_or_default() > window) {
+			self.samples.pop_front();
+		}
+	}
+
+	pub fn record(&mut self, alert: &Alert) {
+		let now = SystemTime::now();
+		self.last_notified = Some(now);
+		self.history.push_back(Notification {
+			at: now,
+			severity: alert.severity,
+			resolved: alert.resolved,
+			message: alert.message.clone(),
+		});
+		while self.history.len() > HISTORY_LEN {
+			self.history.pop_front();
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+	#[default]
+	Resolved,
+	Firing,
+}
+
+/// An ongoing alert condition, remembered so that resolving it can report how long it lasted and how bad it got
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Incident {
+	pub started: SystemTime,
+	/// Severity of the latest alert sent for it
+	pub severity: Severity,
+	/// Rank of the highest threshold tier alerted for
+	pub tier: u32,
+	/// Worst observed value, in the check's own unit
+	pub peak: u64,
+	pub peak_display: String,
+	pub last_notified: SystemTime,
+}
+
+impl Incident {
+	pub fn duration(&self) -> Duration {
+		self.started.elapsed().unwrap_or_default()
+	}
+
+	/// Whether a reminder is due, given the configured re-notify interval
+	pub fn should_renotify(&self, every: Option<Duration>) -> bool {
+		every.is_some_and(|every| self.last_notified.elapsed().unwrap_or_default() >= every)
+	}
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Sample {
+	pub at: SystemTime,
+	pub value: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Notification {
+	pub at: SystemTime,
+	pub severity: Severity,
+	pub resolved: bool,
+	pub message: String,
+}
@@ -0,0 +1,94 @@
+//! Units and their state, as told by systemd over D-Bus.
+use std::process::Stdio;
+
+use color_eyre::eyre::Result;
+use tokio::process::Command;
+use zbus::{Connection, proxy, proxy::CacheProperties, zvariant::OwnedObjectPath};
+
+/// Entry of `ListUnits`: name, description, load state, active state, sub state, followed unit, object path, job ID, job type and job path
+pub type UnitStatus = (String, String, String, String, String, String, OwnedObjectPath, u32, String, OwnedObjectPath);
+
+#[proxy(
+	interface = "org.freedesktop.systemd1.Manager",
+	default_service = "org.freedesktop.systemd1",
+	default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+	fn list_units(&self) -> zbus::Result<Vec<UnitStatus>>;
+}
+
+#[proxy(interface = "org.freedesktop.systemd1.Service", default_service = "org.freedesktop.systemd1")]
+trait Service {
+	#[zbus(property, name = "NRestarts")]
+	fn n_restarts(&self) -> zbus::Result<u32>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unit {
+	/// e.g. "nginx.service"
+	pub name: String,
+	/// "active", "failed", "activating", ...
+	pub active_state: String,
+	/// Unit type specific, e.g. "running" or "auto-restart" for services
+	pub sub_state: String,
+	pub path: OwnedObjectPath,
+}
+
+impl Unit {
+	pub fn is_service(&self) -> bool {
+		self.name.ends_with(".service")
+	}
+}
+
+pub struct Systemd {
+	connection: Connection,
+	manager: ManagerProxy<'static>,
+}
+
+impl Systemd {
+	/// Talks to the system instance of systemd
+	pub async fn system() -> Result<Self> {
+		Self::with_connection(Connection::system().await?).await
+	}
+
+	/// Talks to whatever serves the systemd API on `connection`
+	pub async fn with_connection(connection: Connection) -> Result<Self> {
+		let manager = ManagerProxy::new(&connection).await?;
+		Ok(Self { connection, manager })
+	}
+
+	/// Units systemd has loaded, which includes failed ones until they're reset
+	pub async fn units(&self) -> Result<Vec<Unit>> {
+		let units = self.manager.list_units().await?;
+		Ok(units
+			.into_iter()
+			.map(|(name, _description, _load_state, active_state, sub_state, _followed, path, ..)| Unit {
+				name,
+				active_state,
+				sub_state,
+				path,
+			})
+			.collect())
+	}
+
+	/// How many times systemd has automatically restarted the service since it was last started by hand
+	pub async fn n_restarts(&self, unit: &Unit) -> Result<u32> {
+		// properties are read once per check, so there's no point in subscribing to their changes
+		let service = ServiceProxy::builder(&self.connection)
+			.path(unit.path.clone())?
+			.cache_properties(CacheProperties::No)
+			.build()
+			.await?;
+		Ok(service.n_restarts().await?)
+	}
+}
+
+/// Last `lines` lines `unit` logged to the journal
+pub async fn journal_tail(unit: &str, lines: usize) -> Result<String> {
+	let output = Command::new("journalctl")
+		.args(["--unit", unit, "--lines", &lines.to_string(), "--no-pager", "--quiet", "--output=short-iso"])
+		.stdin(Stdio::null())
+		.output()
+		.await?;
+	Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_owned())
+}
@@ -0,0 +1,90 @@
+//! Keeping filesystem walks out of the way of the services running on the same host.
+use std::{
+	io,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use tracing::warn;
+
+use crate::config::ThrottleConfig;
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+/// Runs `f` on a thread of its own with priorities lowered per `config`.
+///
+/// A thread of its own, because without CAP_SYS_NICE the priority can't be raised back after. Threads `f` spawns inherit the lowered priority.
+pub async fn spawn_throttled<T, F>(config: &ThrottleConfig, f: F) -> Result<T>
+where
+	T: Send + 'static,
+	F: FnOnce() -> T + Send + 'static, {
+	let config = config.clone();
+	let (tx, rx) = tokio::sync::oneshot::channel();
+	std::thread::Builder::new().name("throttled-walk".to_owned()).spawn(move || {
+		lower_priority(&config);
+		let _ = tx.send(f());
+	})?;
+	Ok(rx.await?)
+}
+
+/// Lowers CPU and IO priority of the calling thread
+pub fn lower_priority(config: &ThrottleConfig) {
+	if config.nice > 0 {
+		// SAFETY: gettid can't fail, and setpriority only reads its integer arguments
+		let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, config.nice.into()) };
+		if ret == -1 {
+			warn!("Failed to set nice {}: {}", config.nice, io::Error::last_os_error());
+		}
+	}
+	if config.io_idle {
+		// SAFETY: ioprio_set only reads its integer arguments; 0 for `who` is the calling thread
+		let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) };
+		if ret == -1 {
+			warn!("Failed to switch to idle IO priority: {}", io::Error::last_os_error());
+		}
+	}
+}
+
+/// How far a limiter may fall behind its pace before it stops catching up
+const MAX_BURST: Duration = Duration::from_secs(1);
+
+/// Paces walks to at most `per_sec` entries per second, shared between all threads using it
+#[derive(Debug)]
+pub struct RateLimiter {
+	per_sec: Option<u32>,
+	/// When pacing (re)started, and how many entries were looked at since
+	progress: Mutex<(Instant, u64)>,
+}
+
+impl RateLimiter {
+	pub fn new(per_sec: Option<u32>) -> Self {
+		Self {
+			per_sec,
+			progress: Mutex::new((Instant::now(), 0)),
+		}
+	}
+
+	/// Counts one entry, sleeping for as long as it takes to stay under the limit
+	pub fn entry(&self) {
+		let Some(per_sec) = self.per_sec.filter(|&p| p > 0) else {
+			return;
+		};
+		let pace = |(started, entries): (Instant, u64)| started + Duration::from_secs_f64(entries as f64 / f64::from(per_sec));
+		let now = Instant::now();
+		let due = {
+			let mut progress = self.progress.lock().unwrap();
+			// a limiter kept around between walks mustn't save up the time it sat idle for a burst after
+			if pace(*progress) + MAX_BURST < now {
+				*progress = (now, 0);
+			}
+			progress.1 += 1;
+			pace(*progress)
+		};
+		if due > now {
+			std::thread::sleep(due - now);
+		}
+	}
+}
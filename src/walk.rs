@@ -0,0 +1,237 @@
+//! Filesystem walking for size accounting.
+use std::{
+	cmp::Reverse,
+	collections::{BinaryHeap, HashSet},
+	fs,
+	os::unix::fs::MetadataExt,
+	path::{Path, PathBuf},
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicU64, Ordering},
+	},
+};
+
+use color_eyre::eyre::Result;
+use globset::GlobSet;
+use rayon::{ThreadPool, ThreadPoolBuilder, prelude::*};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{
+	config::ThrottleConfig,
+	throttle::{RateLimiter, lower_priority},
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct DirUsage {
+	/// Sum of file lengths in bytes, as `du --apparent-size` counts them
+	pub apparent: u64,
+	/// Bytes actually taken on disk, as plain `du` counts them: less than `apparent` for sparse files, more for lots of small ones
+	pub allocated: u64,
+	/// Largest immediate subdirectories by apparent size, biggest first
+	pub top_dirs: Vec<(PathBuf, u64)>,
+	/// Largest files anywhere in the tree, biggest first
+	pub top_files: Vec<(PathBuf, u64)>,
+	/// Entries that couldn't be read, and so aren't counted
+	pub errors: u64,
+}
+
+impl DirUsage {
+	/// Human-readable listing of the top consumers, for attaching to alerts
+	pub fn breakdown(&self) -> String {
+		let list = |entries: &[(PathBuf, u64)]| {
+			entries
+				.iter()
+				.map(|(path, size)| format!("  {}  {}", InfoSize::from_parts(*size, InfoSizeUnit::Byte), path.display()))
+				.collect::<Vec<_>>()
+				.join("\n")
+		};
+
+		let mut sections = Vec::new();
+		if !self.top_dirs.is_empty() {
+			sections.push(format!("Largest directories:\n{}", list(&self.top_dirs)));
+		}
+		if !self.top_files.is_empty() {
+			sections.push(format!("Largest files:\n{}", list(&self.top_files)));
+		}
+		sections.join("\n")
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WalkOptions {
+	/// How many of the largest subdirectories and files to keep track of
+	pub top_n: usize,
+	/// Skip directories on other filesystems than the walked one (e.g. /proc under /)
+	pub one_file_system: bool,
+	/// Matched against paths relative to the walked directory; matching files and whole directories are left out
+	pub exclude: GlobSet,
+	/// Threads to walk with, see [walk_pool]; rayon's global pool if None
+	pub pool: Option<Arc<ThreadPool>>,
+	/// See [ThrottleConfig::entries_per_sec](crate::config::ThrottleConfig::entries_per_sec)
+	pub entries_per_sec: Option<u32>,
+}
+
+impl WalkOptions {
+	/// Whether `path` under the walked `root` matches `exclude`
+	pub fn excludes(&self, root: &Path, path: &Path) -> bool {
+		!self.exclude.is_empty() && path.strip_prefix(root).is_ok_and(|relative| self.exclude.is_match(relative))
+	}
+}
+
+/// Pool for all walks to share, instead of each starting threads of its own. `threads` of 0 is one per core.
+///
+/// Its threads lower their priorities per `throttle` themselves, as they aren't spawned from the throttled thread a walk is started on.
+pub fn walk_pool(threads: usize, throttle: &ThrottleConfig) -> Result<Arc<ThreadPool>> {
+	let throttle = throttle.clone();
+	let pool = ThreadPoolBuilder::new()
+		.num_threads(threads)
+		.thread_name(|i| format!("walk-{i}"))
+		.start_handler(move |_| lower_priority(&throttle))
+		.build()?;
+	Ok(Arc::new(pool))
+}
+
+/// Sums up the size of everything under `path`, keeping track of the largest subdirectories and files.
+///
+/// Subdirectories are walked in parallel. Symlinks are not followed, and files hardlinked several times within the tree are counted once.
+pub fn dir_usage(path: &Path, options: &WalkOptions) -> Result<DirUsage> {
+	let root_meta = fs::symlink_metadata(path)?;
+	let walker = Walker {
+		root: path,
+		top_n: options.top_n,
+		device: options.one_file_system.then_some(root_meta.dev()),
+		options,
+		seen: Mutex::new(HashSet::new()),
+		errors: AtomicU64::new(0),
+		limiter: RateLimiter::new(options.entries_per_sec),
+	};
+	let entries: Vec<PathBuf> = fs::read_dir(path)?.filter_map(|entry| walker.entry_path(entry)).collect();
+	let walk = || {
+		entries
+			.into_par_iter()
+			.map(|path| match fs::symlink_metadata(&path) {
+				Ok(meta) if meta.is_dir() => {
+					let tally = walker.dir_tally(&path, &meta);
+					let dirs = vec![(path, tally.apparent)];
+					(tally, dirs)
+				}
+				Ok(meta) => (walker.file_tally(path, &meta), Vec::new()),
+				Err(_) => {
+					walker.error();
+					(Tally::default(), Vec::new())
+				}
+			})
+			.reduce(
+				|| (Tally::default(), Vec::new()),
+				|(a, mut a_dirs), (b, b_dirs)| {
+					a_dirs.extend(b_dirs);
+					(walker.merge(a, b), a_dirs)
+				},
+			)
+	};
+	let (tally, mut dirs) = match &options.pool {
+		Some(pool) => pool.install(walk),
+		None => walk(),
+	};
+
+	dirs.sort_by_key(|(_, size)| Reverse(*size));
+	dirs.truncate(options.top_n);
+	let mut top_files: Vec<_> = tally.top_files.into_iter().map(|Reverse((size, path))| (path, size)).collect();
+	top_files.sort_by_key(|(_, size)| Reverse(*size));
+
+	Ok(DirUsage {
+		apparent: tally.apparent,
+		allocated: tally.allocated,
+		top_dirs: dirs,
+		top_files,
+		errors: walker.errors.into_inner(),
+	})
+}
+
+struct Walker<'a> {
+	root: &'a Path,
+	top_n: usize,
+	/// Only descend into directories on this device
+	device: Option<u64>,
+	options: &'a WalkOptions,
+	/// (dev, inode) of the already counted files that have more than one hardlink
+	seen: Mutex<HashSet<(u64, u64)>>,
+	errors: AtomicU64,
+	limiter: RateLimiter,
+}
+
+/// Sizes of a subtree
+#[derive(Default)]
+struct Tally {
+	apparent: u64,
+	allocated: u64,
+	/// Min-heap, so the smallest of the current top is the one to evict
+	top_files: BinaryHeap<Reverse<(u64, PathBuf)>>,
+}
+
+impl Walker<'_> {
+	fn error(&self) {
+		self.errors.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Path of a directory entry, unless it can't be read or is excluded
+	fn entry_path(&self, entry: std::io::Result<fs::DirEntry>) -> Option<PathBuf> {
+		self.limiter.entry();
+		let Ok(entry) = entry else {
+			self.error();
+			return None;
+		};
+		let path = entry.path();
+		(!self.options.excludes(self.root, &path)).then_some(path)
+	}
+
+	fn dir_tally(&self, path: &Path, meta: &fs::Metadata) -> Tally {
+		if self.device.is_some_and(|dev| meta.dev() != dev) {
+			return Tally::default();
+		}
+		let Ok(entries) = fs::read_dir(path) else {
+			self.error();
+			return Tally::default();
+		};
+
+		let mut tally = Tally::default();
+		let mut subdirs = Vec::new();
+		for path in entries.filter_map(|entry| self.entry_path(entry)) {
+			match fs::symlink_metadata(&path) {
+				Ok(meta) if meta.is_dir() => subdirs.push((path, meta)),
+				Ok(meta) => tally = self.merge(tally, self.file_tally(path, &meta)),
+				Err(_) => self.error(),
+			}
+		}
+		let subdirs = subdirs
+			.into_par_iter()
+			.map(|(path, meta)| self.dir_tally(&path, &meta))
+			.reduce(Tally::default, |a, b| self.merge(a, b));
+		self.merge(tally, subdirs)
+	}
+
+	fn file_tally(&self, path: PathBuf, meta: &fs::Metadata) -> Tally {
+		if meta.nlink() > 1 && !self.seen.lock().unwrap().insert((meta.dev(), meta.ino())) {
+			return Tally::default();
+		}
+		let mut top_files = BinaryHeap::new();
+		if self.top_n > 0 {
+			top_files.push(Reverse((meta.len(), path)));
+		}
+		Tally {
+			apparent: meta.len(),
+			allocated: meta.blocks() * 512,
+			top_files,
+		}
+	}
+
+	fn merge(&self, mut a: Tally, b: Tally) -> Tally {
+		a.apparent += b.apparent;
+		a.allocated += b.allocated;
+		a.top_files.extend(b.top_files);
+		while a.top_files.len() > self.top_n {
+			a.top_files.pop();
+		}
+		a
+	}
+}
@@ -0,0 +1,248 @@
+use std::{
+	collections::VecDeque,
+	fs,
+	path::Path,
+	sync::{
+		Arc,
+		atomic::{AtomicU16, Ordering},
+	},
+	time::{Duration, SystemTime},
+};
+
+use server_upkeep::{
+	alerts::{Alerter, Observation, growth_rate, time_to_full},
+	checks::{check_eta, evaluate_pct},
+	config::{AlertsConfig, MonitorConfig, SeverityTiers, SinkConfig, SinkKind, WebhookConfig},
+	notify::Notifiers,
+	state::{Sample, StateStore},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+	http_stand_in::{Request, recording_stand_in},
+	walk::scratch_dir,
+};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// [Alerter] delivering through a webhook to a [recording_stand_in], for following what it sends
+pub struct Recorder {
+	pub alerter: Alerter,
+	/// What the sink answers with; set to an error status to make deliveries fail
+	pub status: Arc<AtomicU16>,
+	requests: mpsc::UnboundedReceiver<Request>,
+}
+
+impl Recorder {
+	pub async fn new(store: &Path) -> Self {
+		let (addr, status, requests) = recording_stand_in().await;
+		let sink = SinkConfig {
+			name: None,
+			kind: SinkKind::Webhook(WebhookConfig {
+				url: format!("http://{addr}/"),
+				method: "POST".to_owned(),
+				headers: Default::default(),
+				body: r#"{"check": "{{check}}", "status": "{{status}}", "severity": "{{severity}}", "message": "{{message}}"}"#.to_owned(),
+			}),
+		};
+		let notifiers = Notifiers::from_config(&AlertsConfig {
+			sinks: vec![sink],
+			..Default::default()
+		})
+		.unwrap();
+		Self {
+			alerter: Alerter::new(notifiers, StateStore::load(store.to_owned()).unwrap()),
+			status,
+			requests,
+		}
+	}
+
+	/// Alerts delivery was attempted for since the last call, whether the sink accepted them or not
+	pub fn sent(&mut self) -> Vec<serde_json::Value> {
+		std::iter::from_fn(|| self.requests.try_recv().ok())
+			.map(|request| serde_json::from_str(&request.body).unwrap())
+			.collect()
+	}
+}
+
+/// Observation of / at `pct` disk usage, against thresholds 80/90/95 resetting below 70
+pub fn disk_usage(pct: u8) -> Observation {
+	Observation {
+		check: "disk:/".to_owned(),
+		subject: "/ disk usage".to_owned(),
+		value: u64::from(pct),
+		display: format!("{pct}%"),
+		evaluation: evaluate_pct(pct, &[80, 90, 95], 70, &SeverityTiers::default()),
+		renotify: None,
+		context: None,
+	}
+}
+
+#[test]
+fn projects_time_to_full() {
+	let start = SystemTime::now() - Duration::from_secs(3 * 3600);
+	// 1000 bytes/hour, with some noise
+	let samples: VecDeque<Sample> = [(0, 10_000), (1, 11_050), (2, 11_950), (3, 13_000)]
+		.into_iter()
+		.map(|(hour, value)| Sample {
+			at: start + Duration::from_secs(hour * 3600),
+			value,
+		})
+		.collect();
+
+	let rate = growth_rate(&samples).unwrap();
+	assert!((rate * 3600.0 - 1000.0).abs() < 20.0, "{rate}");
+
+	let eta = time_to_full(6000, Some(rate)).unwrap();
+	assert!((5.9..6.1).contains(&(eta.as_secs_f64() / 3600.0)), "{eta:?}");
+
+	assert!(growth_rate(&samples.iter().take(2).copied().collect()).is_none(), "two points don't make a trend");
+	assert!(time_to_full(6000, Some(-rate)).is_none(), "shrinking never fills up");
+	assert_eq!(time_to_full(u64::MAX, Some(1e-30)), Some(Duration::MAX), "saturates instead of overflowing");
+}
+
+#[tokio::test]
+async fn eta_holds_between_horizon_and_reset() {
+	let mut recorder = Recorder::new(&scratch_dir("eta_hold").join("alerts.json")).await;
+	let config = MonitorConfig {
+		eta_horizon: Some(HOUR * 24),
+		..Default::default()
+	};
+	let eta = async |recorder: &mut Recorder, hours| {
+		check_eta(&config, &recorder.alerter, "disk:/", "/", Some(HOUR * hours)).await.unwrap();
+		recorder.sent()
+	};
+
+	let [fired] = eta(&mut recorder, 10).await.try_into().unwrap();
+	assert_eq!(fired["message"], "time until / is full at ~10h 0m (crossed 1d 0h threshold)");
+	assert!(eta(&mut recorder, 30).await.is_empty(), "within the reset band of 1.5 × the horizon");
+	assert!(eta(&mut recorder, 20).await.is_empty());
+	let [resolved] = eta(&mut recorder, 40).await.try_into().unwrap();
+	assert_eq!(resolved["status"], "resolved");
+}
+
+#[tokio::test]
+async fn resolves_with_duration_and_peak() {
+	let mut recorder = Recorder::new(&scratch_dir("resolves").join("alerts.json")).await;
+
+	recorder.alerter.observe(disk_usage(91)).await.unwrap();
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("disk:/")));
+	assert_eq!(fired["message"], "/ disk usage at 91% (crossed 90% threshold)");
+
+	// higher, but within the same tier
+	recorder.alerter.observe(disk_usage(93)).await.unwrap();
+	assert!(recorder.sent().is_empty());
+
+	recorder.alerter.observe(disk_usage(40)).await.unwrap();
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!(resolved["status"], "resolved");
+	assert_eq!(resolved["severity"], fired["severity"]);
+	assert_eq!(resolved["message"], "/ disk usage back to 40% after 0m (peaked at 93%)");
+
+	recorder.alerter.observe(disk_usage(40)).await.unwrap();
+	assert!(recorder.sent().is_empty(), "resolves only once");
+	assert!(recorder.alerter.firing("disk:").is_empty());
+}
+
+#[tokio::test]
+async fn imports_legacy_last_pct_used() {
+	let dir = scratch_dir("legacy_state");
+	let legacy = dir.join("last_pct_used");
+	fs::write(&legacy, "90").unwrap();
+	let mut store = StateStore::load(dir.join("alerts.json")).unwrap();
+	store.import_last_pct_used(&legacy, &SeverityTiers::default()).unwrap();
+	assert!(!legacy.exists());
+	let incident = store.get("disk:/").and_then(|s| s.incident.as_ref()).unwrap();
+	assert_eq!((incident.tier, incident.peak_display.as_str()), (90, "90%"));
+
+	// already alerted for that tier before upgrading
+	let mut recorder = Recorder::new(&dir.join("alerts.json")).await;
+	recorder.alerter.observe(disk_usage(91)).await.unwrap();
+	assert!(recorder.sent().is_empty());
+	recorder.alerter.observe(disk_usage(96)).await.unwrap();
+	let [escalated] = recorder.sent().try_into().unwrap();
+	assert_eq!(escalated["severity"], "emergency");
+	assert_eq!(escalated["message"], "/ disk usage at 96% (crossed 95% threshold)");
+}
+
+#[tokio::test]
+async fn deduplicates_across_restarts() {
+	let store = scratch_dir("dedup").join("alerts.json");
+	let mut recorder = Recorder::new(&store).await;
+	recorder.alerter.observe(disk_usage(91)).await.unwrap();
+	recorder.alerter.observe(disk_usage(92)).await.unwrap();
+	assert_eq!(recorder.sent().len(), 1);
+
+	let mut restarted = Recorder::new(&store).await;
+	restarted.alerter.observe(disk_usage(92)).await.unwrap();
+	assert!(restarted.sent().is_empty(), "incident is picked up from the store");
+	// between reset and the first tier, the incident is kept
+	restarted.alerter.observe(disk_usage(75)).await.unwrap();
+	restarted.alerter.observe(disk_usage(91)).await.unwrap();
+	assert!(restarted.sent().is_empty());
+	assert_eq!(restarted.alerter.firing("disk:"), ["disk:/"]);
+}
+
+#[tokio::test]
+async fn renotifies_while_firing() {
+	let mut recorder = Recorder::new(&scratch_dir("renotify").join("alerts.json")).await;
+	let reminding = |pct| Observation {
+		renotify: Some(Duration::ZERO),
+		..disk_usage(pct)
+	};
+
+	recorder.alerter.observe(reminding(91)).await.unwrap();
+	recorder.alerter.observe(reminding(92)).await.unwrap();
+	let [fired, reminder] = recorder.sent().try_into().unwrap();
+	assert_eq!(fired["message"], "/ disk usage at 91% (crossed 90% threshold)");
+	assert_eq!(reminder["status"], "firing");
+	assert_eq!(reminder["message"], "/ disk usage still at 92%, over 90% for 0m");
+
+	// not while holding, nor once it's no longer due
+	recorder.alerter.observe(reminding(75)).await.unwrap();
+	recorder.alerter.observe(disk_usage(92)).await.unwrap();
+	assert!(recorder.sent().is_empty());
+}
+
+#[tokio::test]
+async fn escalates_through_tiers() {
+	let mut recorder = Recorder::new(&scratch_dir("escalates").join("alerts.json")).await;
+
+	for pct in [81, 85, 91, 96, 93] {
+		recorder.alerter.observe(disk_usage(pct)).await.unwrap();
+	}
+	let sent = recorder.sent();
+	assert_eq!(
+		sent.iter().map(|a| (a["severity"].as_str().unwrap(), a["message"].as_str().unwrap())).collect::<Vec<_>>(),
+		[
+			("warning", "/ disk usage at 81% (crossed 80% threshold)"),
+			("critical", "/ disk usage at 91% (crossed 90% threshold)"),
+			("emergency", "/ disk usage at 96% (crossed 95% threshold)"),
+		],
+		"only crossing a higher tier alerts again, dropping back a tier doesn't"
+	);
+
+	recorder.alerter.observe(disk_usage(50)).await.unwrap();
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!(resolved["severity"], "emergency");
+	assert_eq!(resolved["message"], "/ disk usage back to 50% after 0m (peaked at 96%)");
+}
+
+#[tokio::test]
+async fn failed_send_is_retried() {
+	let mut recorder = Recorder::new(&scratch_dir("failed_send").join("alerts.json")).await;
+	recorder.status.store(500, Ordering::SeqCst);
+
+	assert!(recorder.alerter.observe(disk_usage(91)).await.is_err());
+	assert_eq!(recorder.sent().len(), 1);
+	assert!(recorder.alerter.firing("disk:").is_empty(), "not marked notified");
+
+	recorder.status.store(200, Ordering::SeqCst);
+	recorder.alerter.observe(disk_usage(91)).await.unwrap();
+	let [retried] = recorder.sent().try_into().unwrap();
+	assert_eq!(retried["message"], "/ disk usage at 91% (crossed 90% threshold)");
+	recorder.alerter.observe(disk_usage(91)).await.unwrap();
+	assert!(recorder.sent().is_empty());
+	assert_eq!(recorder.alerter.firing("disk:"), ["disk:/"]);
+}
@@ -0,0 +1,164 @@
+use std::{path::PathBuf, time::Duration};
+
+use server_upkeep::{
+	config::{AlertsConfig, AppConfig, MonitorConfig, MountConfig, SeverityTiers, SinkConfig, SinkKind, TelegramConfig, WatchedDir},
+	notify::Severity,
+};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+#[test]
+fn validates_threshold_tiers() {
+	assert!(MonitorConfig::default().validate().is_ok());
+
+	let config = MonitorConfig {
+		disk_thresholds: vec![50, 90, 80],
+		..Default::default()
+	};
+	assert!(config.validate().is_err(), "tiers must ascend");
+
+	let config = MonitorConfig {
+		disk_reset: 50,
+		..Default::default()
+	};
+	assert!(config.validate().is_err(), "reset must sit below the first tier");
+
+	let config = MonitorConfig {
+		interval: Duration::ZERO,
+		..Default::default()
+	};
+	assert!(config.validate().is_err(), "a zero interval would check in a busy loop");
+
+	let config = MonitorConfig {
+		sustain: Duration::ZERO,
+		..Default::default()
+	};
+	assert!(config.validate().is_err());
+
+	// per-mount tiers are checked against the global reset they'd fall back to
+	let mut config = MonitorConfig::default();
+	config.disk_mounts.insert(
+		"/data".to_owned(),
+		MountConfig {
+			thresholds: Some(vec![40, 80]),
+			..Default::default()
+		},
+	);
+	assert!(config.validate().is_err());
+}
+
+#[test]
+fn maps_tiers_to_severity() {
+	let tiers = SeverityTiers {
+		warning: 70,
+		critical: 90,
+		emergency: 95,
+	};
+	assert_eq!(tiers.severity(50), Severity::Info);
+	assert_eq!(tiers.severity(70), Severity::Warning);
+	assert_eq!(tiers.severity(89), Severity::Warning);
+	assert_eq!(tiers.severity(90), Severity::Critical);
+	assert_eq!(tiers.severity(100), Severity::Emergency);
+
+	// the first swap tier is worth more than a note
+	let config = MonitorConfig::default();
+	assert_eq!(
+		config.swap_thresholds.iter().map(|&t| config.swap_severity.severity(t)).collect::<Vec<_>>(),
+		[Severity::Warning, Severity::Critical, Severity::Emergency]
+	);
+
+	let config = MonitorConfig {
+		memory_severity: SeverityTiers {
+			warning: 90,
+			critical: 80,
+			emergency: 95,
+		},
+		..Default::default()
+	};
+	assert!(config.validate().is_err(), "severity tiers must ascend");
+
+	let mut config = MonitorConfig::default();
+	config.disk_mounts.insert(
+		"/data".to_owned(),
+		MountConfig {
+			severity: Some(SeverityTiers {
+				warning: 70,
+				critical: 99,
+				emergency: 95,
+			}),
+			..Default::default()
+		},
+	);
+	assert!(config.validate().is_err(), "per-mount severity tiers are validated too");
+}
+
+#[test]
+fn watches_state_dir_by_default() {
+	let config = MonitorConfig::default();
+	let [dir] = config.watched_dirs.as_slice() else { panic!() };
+	assert_eq!(dir.check_id(), "state_dir");
+	assert_eq!(dir.expanded_path().unwrap(), dirs::state_dir().unwrap());
+}
+
+#[test]
+fn migrates_legacy_max_size() {
+	let mut config = MonitorConfig {
+		max_size: Some(InfoSize::from_parts(5, InfoSizeUnit::Gigabyte)),
+		max_size_severity: Some(Severity::Warning),
+		..Default::default()
+	};
+	config.migrate_legacy().unwrap();
+	assert_eq!(config.watched_dirs[0].max_size, InfoSize::from_parts(5, InfoSizeUnit::Gigabyte));
+	assert_eq!(config.watched_dirs[0].severity, Severity::Warning);
+	assert!(config.max_size.is_none());
+
+	let mut config = MonitorConfig {
+		watched_dirs: Vec::new(),
+		max_size: Some(InfoSize::from_parts(5, InfoSizeUnit::Gigabyte)),
+		..Default::default()
+	};
+	assert!(config.migrate_legacy().is_err(), "nowhere to apply it to");
+}
+
+#[test]
+fn migrates_legacy_telegram() {
+	let telegram = TelegramConfig {
+		bot_token: "token".to_owned(),
+		alerts_chat: "chat".to_owned(),
+	};
+	let mut config = AppConfig {
+		telegram: Some(telegram.clone()),
+		..Default::default()
+	};
+	assert!(config.validate().is_err(), "no sinks");
+	config.migrate_legacy().unwrap();
+	assert!(config.telegram.is_none());
+	let [sink] = config.alerts.sinks.as_slice() else { panic!("{:?}", config.alerts.sinks) };
+	assert!(matches!(&sink.kind, SinkKind::Telegram(t) if t.alerts_chat == "chat"));
+	assert!(config.validate().is_ok());
+
+	let mut config = AppConfig {
+		telegram: Some(telegram.clone()),
+		alerts: AlertsConfig {
+			sinks: vec![SinkConfig {
+				name: None,
+				kind: SinkKind::Telegram(telegram),
+			}],
+			..Default::default()
+		},
+		..Default::default()
+	};
+	assert!(config.migrate_legacy().is_err(), "a telegram sink is configured already");
+}
+
+#[test]
+fn expands_home_only_as_a_whole_component() {
+	let home = dirs::home_dir().unwrap();
+	let dir = |path: &str| WatchedDir {
+		path: path.to_owned(),
+		..MonitorConfig::default().watched_dirs.remove(0)
+	};
+	assert_eq!(dir("~").expanded_path().unwrap(), home);
+	assert_eq!(dir("~/.cache").expanded_path().unwrap(), home.join(".cache"));
+	assert_eq!(dir("/srv/~data").expanded_path().unwrap(), PathBuf::from("/srv/~data"));
+	assert!(dir("~alice/x").expanded_path().is_err());
+}
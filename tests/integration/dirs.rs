@@ -0,0 +1,52 @@
+use std::fs;
+
+use server_upkeep::{
+	checks::dirs::check_watched_dir,
+	config::{MonitorConfig, ThrottleConfig, WatchedDir},
+	notify::Severity,
+	walk::walk_pool,
+};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{alerts::Recorder, walk::scratch_dir};
+
+#[tokio::test]
+async fn alerts_on_watched_dir_size() {
+	let dir = scratch_dir("watched_dir");
+	let mut recorder = Recorder::new(&scratch_dir("watched_dir_state").join("alerts.json")).await;
+	let (config, throttle) = (MonitorConfig::default(), ThrottleConfig::default());
+	let pool = walk_pool(1, &throttle).unwrap();
+	let watched = WatchedDir {
+		path: dir.to_string_lossy().into_owned(),
+		id: Some("uploads".to_owned()),
+		max_size: InfoSize::from_parts(1000, InfoSizeUnit::Byte),
+		reset: Some(InfoSize::from_parts(500, InfoSizeUnit::Byte)),
+		severity: Severity::Critical,
+		renotify: None,
+		exclude: Vec::new(),
+		one_file_system: false,
+		incremental: false,
+		interval: None,
+	};
+	let mut check_at = async |size: usize| {
+		fs::write(dir.join("data"), vec![0; size]).unwrap();
+		check_watched_dir(&config, &throttle, &pool, &recorder.alerter, &watched, None).await.unwrap();
+		recorder.sent()
+	};
+
+	assert!(check_at(800).await.is_empty());
+
+	let [fired] = check_at(1500).await.try_into().unwrap();
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("uploads")));
+	assert_eq!(fired["severity"], "critical");
+	assert!(fired["message"].as_str().unwrap().contains(&dir.join("data").display().to_string()), "lists the largest files");
+
+	// under the budget, but not yet under the reset
+	assert!(check_at(800).await.is_empty());
+
+	let [resolved] = check_at(300).await.try_into().unwrap();
+	assert_eq!((resolved["status"].as_str(), resolved["check"].as_str()), (Some("resolved"), Some("uploads")));
+	assert!(check_at(300).await.is_empty());
+
+	fs::remove_dir_all(&dir).unwrap();
+}
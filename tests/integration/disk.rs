@@ -0,0 +1,131 @@
+use server_upkeep::{
+	alerts::{Evaluation, Observation},
+	checks::disk::{evaluate_free, inode_usage},
+	config::{FreeSpaceTier, MonitorConfig, MountConfig},
+	notify::Severity,
+};
+use v_utils::utils::{InfoSize, InfoSizeUnit};
+
+use crate::{
+	alerts::{Recorder, disk_usage},
+	walk::scratch_dir,
+};
+
+/// Of a million inodes
+fn inodes(config: &MonitorConfig, mount_point: &str, pct: u64) -> Observation {
+	inode_usage(config, mount_point, pct * 10_000, 1_000_000).unwrap()
+}
+
+#[tokio::test]
+async fn alerts_on_inodes_apart_from_blocks() {
+	let mut recorder = Recorder::new(&scratch_dir("inodes").join("alerts.json")).await;
+	let mut config = MonitorConfig::default();
+	config.disk_mounts.insert(
+		"/data".to_owned(),
+		MountConfig {
+			inode_thresholds: Some(vec![50, 60]),
+			inode_reset: Some(40),
+			..Default::default()
+		},
+	);
+	assert!(inode_usage(&config, "/", 0, 0).is_none(), "allocated dynamically");
+
+	recorder.alerter.observe(disk_usage(91)).await.unwrap();
+	recorder.alerter.observe(inodes(&config, "/", 85)).await.unwrap();
+	let [disk, fired] = recorder.sent().try_into().unwrap();
+	assert_eq!(disk["check"], "disk:/");
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("inodes:/")));
+	assert_eq!(fired["message"], "/ inode usage at 85% (crossed 80% threshold)");
+
+	// the next tier up escalates
+	recorder.alerter.observe(inodes(&config, "/", 91)).await.unwrap();
+	let [escalated] = recorder.sent().try_into().unwrap();
+	assert_eq!(escalated["message"], "/ inode usage at 91% (crossed 90% threshold)");
+
+	// between the reset and the first tier, it holds
+	recorder.alerter.observe(inodes(&config, "/", 78)).await.unwrap();
+	assert!(recorder.sent().is_empty());
+
+	recorder.alerter.observe(inodes(&config, "/", 70)).await.unwrap();
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!((resolved["status"].as_str(), resolved["check"].as_str()), (Some("resolved"), Some("inodes:/")));
+	assert_eq!(recorder.alerter.firing("disk:"), ["disk:/"], "block usage is alerted on separately");
+	assert!(recorder.alerter.firing("inodes:").is_empty());
+
+	// per-mount thresholds
+	recorder.alerter.observe(inodes(&config, "/data", 55)).await.unwrap();
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!(
+		(fired["check"].as_str(), fired["message"].as_str()),
+		(Some("inodes:/data"), Some("/data inode usage at 55% (crossed 50% threshold)"))
+	);
+	recorder.alerter.observe(inodes(&config, "/data", 45)).await.unwrap();
+	assert!(recorder.sent().is_empty(), "above its own reset");
+	recorder.alerter.observe(inodes(&config, "/data", 30)).await.unwrap();
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!((resolved["status"].as_str(), resolved["check"].as_str()), (Some("resolved"), Some("inodes:/data")));
+}
+
+fn gb(n: u64) -> InfoSize {
+	InfoSize::from_parts(n, InfoSizeUnit::Gigabyte)
+}
+
+/// Below 20GB free warns, below 5GB is critical
+fn free_tiers() -> Vec<FreeSpaceTier> {
+	vec![
+		FreeSpaceTier {
+			below: gb(20),
+			severity: Severity::Warning,
+		},
+		FreeSpaceTier {
+			below: gb(5),
+			severity: Severity::Critical,
+		},
+	]
+}
+
+#[test]
+fn evaluates_free_space_tiers() {
+	let tiers = free_tiers();
+	let firing = |free: u64, reset: Option<InfoSize>| match evaluate_free(gb(free), &tiers, reset) {
+		Evaluation::Firing { tier, severity, threshold } => Some((tier, severity, threshold.unwrap())),
+		_ => None,
+	};
+
+	// the lowest tier crossed wins, with its own severity
+	assert_eq!(firing(10, None), Some((0, Severity::Warning, gb(20).to_string())));
+	assert_eq!(firing(3, None), Some((1, Severity::Critical, gb(5).to_string())));
+
+	// resets at the first tier by default
+	assert!(matches!(evaluate_free(gb(20), &tiers, None), Evaluation::Clear { threshold: Some(t) } if t == gb(20).to_string()));
+	// with a reset above it, what's in between holds
+	assert!(matches!(evaluate_free(gb(25), &tiers, Some(gb(30))), Evaluation::Hold));
+	assert!(matches!(evaluate_free(gb(30), &tiers, Some(gb(30))), Evaluation::Clear { .. }));
+	assert_eq!(firing(19, Some(gb(30))), Some((0, Severity::Warning, gb(20).to_string())));
+}
+
+#[test]
+fn validates_free_space_tiers() {
+	let config = |disk_min_free: Vec<FreeSpaceTier>, disk_min_free_reset: Option<InfoSize>| MonitorConfig {
+		disk_min_free,
+		disk_min_free_reset,
+		..Default::default()
+	};
+	assert!(config(free_tiers(), None).validate().is_ok());
+	assert!(config(free_tiers(), Some(gb(30))).validate().is_ok());
+	assert!(config(free_tiers().into_iter().rev().collect(), None).validate().is_err(), "tiers must descend");
+	assert!(config(vec![free_tiers()[0].clone(), free_tiers()[0].clone()], None).validate().is_err(), "strictly");
+	assert!(config(free_tiers(), Some(gb(10))).validate().is_err(), "reset must not be below the first tier");
+
+	// per mount, against the global reset they'd fall back to
+	let mut config = config(Vec::new(), Some(gb(10)));
+	assert!(config.validate().is_ok());
+	config.disk_mounts.insert(
+		"/data".to_owned(),
+		MountConfig {
+			min_free: Some(free_tiers()),
+			..Default::default()
+		},
+	);
+	assert!(config.validate().is_err());
+}
@@ -0,0 +1,67 @@
+use server_upkeep::{
+	config::{EmailConfig, SmtpTls},
+	notify::{Alert, EmailNotifier, Notifier, Severity},
+};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	net::TcpListener,
+	sync::oneshot,
+};
+
+/// Minimal SMTP listener: accepts a single connection, acks every command, and hands over the first DATA payload it receives.
+async fn smtp_stand_in() -> (u16, oneshot::Receiver<String>) {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let port = listener.local_addr().unwrap().port();
+	let (tx, rx) = oneshot::channel();
+
+	tokio::spawn(async move {
+		let (stream, _) = listener.accept().await.unwrap();
+		let (read, mut write) = stream.into_split();
+		let mut lines = BufReader::new(read).lines();
+		write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+		while let Some(line) = lines.next_line().await.unwrap() {
+			let command = line.to_ascii_uppercase();
+			if command.starts_with("DATA") {
+				write.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await.unwrap();
+				let mut data = String::new();
+				while let Some(line) = lines.next_line().await.unwrap() {
+					if line == "." {
+						break;
+					}
+					data.push_str(&line);
+					data.push('\n');
+				}
+				write.write_all(b"250 OK: queued\r\n").await.unwrap();
+				tx.send(data).unwrap();
+				return;
+			}
+			write.write_all(b"250 OK\r\n").await.unwrap();
+		}
+	});
+
+	(port, rx)
+}
+
+#[tokio::test]
+async fn email_delivers_alert() {
+	let (port, received) = smtp_stand_in().await;
+	let config = EmailConfig {
+		host: "127.0.0.1".to_owned(),
+		port: Some(port),
+		tls: SmtpTls::None,
+		username: None,
+		password: None,
+		from: "upkeep@localhost".to_owned(),
+		to: vec!["oncall@localhost".to_owned(), "backup@localhost".to_owned()],
+	};
+	let notifier = EmailNotifier::new(&config).unwrap();
+
+	notifier.send(&Alert::new("disk:/", Severity::Critical, "91%", "90%", "/ disk usage at 91%")).await.unwrap();
+
+	let data = received.await.unwrap();
+	assert!(data.contains("Subject: [critical] "));
+	assert!(data.contains(": disk:/"));
+	assert!(data.contains("To: oncall@localhost, backup@localhost"));
+	assert!(data.contains("/ disk usage at 91%"));
+}
@@ -0,0 +1,105 @@
+use std::{
+	collections::HashMap,
+	net::SocketAddr,
+	sync::{
+		Arc,
+		atomic::{AtomicU16, Ordering},
+	},
+};
+
+use tokio::{
+	io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+	net::{TcpListener, TcpStream},
+	sync::{mpsc, oneshot},
+};
+
+#[derive(Debug)]
+pub struct Request {
+	pub method: String,
+	pub path: String,
+	/// Lowercased names
+	pub headers: HashMap<String, String>,
+	pub body: String,
+}
+
+/// Minimal HTTP/1.1 server: accepts a single connection, answers its request with `status` and `response_body`, and hands the request over.
+pub async fn http_stand_in(status: u16, response_body: &'static str) -> (SocketAddr, oneshot::Receiver<Request>) {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	let (tx, rx) = oneshot::channel();
+
+	tokio::spawn(async move {
+		let (stream, _) = listener.accept().await.unwrap();
+		let _ = answer(stream, status, response_body, |request| {
+			let _ = tx.send(request);
+		})
+		.await;
+	});
+
+	(addr, rx)
+}
+
+/// Like [http_stand_in], but keeps accepting connections, recording every request.
+/// Each is answered with an empty JSON object and whatever status `status` holds at the time, so tests can make deliveries fail.
+pub async fn recording_stand_in() -> (SocketAddr, Arc<AtomicU16>, mpsc::UnboundedReceiver<Request>) {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	let status = Arc::new(AtomicU16::new(200));
+	let (tx, rx) = mpsc::unbounded_channel();
+
+	let answer_with = Arc::clone(&status);
+	tokio::spawn(async move {
+		while let Ok((stream, _)) = listener.accept().await {
+			let tx = tx.clone();
+			let status = answer_with.load(Ordering::SeqCst);
+			tokio::spawn(async move {
+				let _ = answer(stream, status, "{}", |request| {
+					let _ = tx.send(request);
+				})
+				.await;
+			});
+		}
+	});
+
+	(addr, status, rx)
+}
+
+/// Reads one request, hands it to `record` and only then responds, so it's already recorded by the time the client has its answer
+async fn answer(stream: TcpStream, status: u16, response_body: &str, record: impl FnOnce(Request)) -> std::io::Result<()> {
+	let (read, mut write) = stream.into_split();
+	let mut reader = BufReader::new(read);
+
+	let mut request_line = String::new();
+	reader.read_line(&mut request_line).await?;
+	let mut parts = request_line.split_whitespace();
+	let method = parts.next().unwrap_or_default().to_owned();
+	let path = parts.next().unwrap_or_default().to_owned();
+
+	let mut headers = HashMap::new();
+	loop {
+		let mut line = String::new();
+		reader.read_line(&mut line).await?;
+		let line = line.trim_end();
+		if line.is_empty() {
+			break;
+		}
+		let (name, value) = line.split_once(':').unwrap();
+		headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_owned());
+	}
+
+	let len = headers.get("content-length").map(|l| l.parse().unwrap()).unwrap_or(0);
+	let mut body = vec![0; len];
+	reader.read_exact(&mut body).await?;
+
+	record(Request {
+		method,
+		path,
+		headers,
+		body: String::from_utf8(body).unwrap(),
+	});
+	let response = format!(
+		"HTTP/1.1 {status} Stand-in\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response_body}",
+		response_body.len()
+	);
+	write.write_all(response.as_bytes()).await
+}
@@ -0,0 +1,156 @@
+use std::{
+	fs,
+	path::Path,
+	time::{Duration, Instant},
+};
+
+use server_upkeep::{index::SizeIndex, walk::WalkOptions};
+
+use crate::walk::scratch_dir;
+
+/// Events are applied on a background thread, so give them a moment
+fn eventually(index: &SizeIndex, expected: u64) {
+	let deadline = Instant::now() + Duration::from_secs(5);
+	while index.usage(0).apparent != expected {
+		assert!(Instant::now() < deadline, "index stuck at {} bytes, expected {expected}", index.usage(0).apparent);
+		std::thread::sleep(Duration::from_millis(20));
+	}
+}
+
+#[test]
+fn follows_changes_with_inotify() {
+	let root = scratch_dir("index");
+	fs::create_dir_all(root.join("logs")).unwrap();
+	fs::write(root.join("logs/a"), vec![0; 1000]).unwrap();
+
+	let index = SizeIndex::watch_inotify(&root, &WalkOptions::default()).unwrap();
+	assert!(!index.filesystem_wide());
+	follows_changes(&root, &index);
+}
+
+#[test]
+fn follows_changes_with_fanotify() {
+	let root = scratch_dir("index_fanotify");
+	fs::create_dir_all(root.join("logs")).unwrap();
+	fs::write(root.join("logs/a"), vec![0; 1000]).unwrap();
+
+	let index = SizeIndex::watch(&root, &WalkOptions::default()).unwrap();
+	if !index.filesystem_wide() {
+		eprintln!("fanotify isn't permitted here, only the inotify fallback is covered");
+		fs::remove_dir_all(&root).unwrap();
+		return;
+	}
+	follows_changes(&root, &index);
+}
+
+fn follows_changes(root: &Path, index: &SizeIndex) {
+	assert_eq!(index.usage(0).apparent, 1000);
+
+	fs::write(root.join("logs/a"), vec![0; 1500]).unwrap();
+	eventually(index, 1500);
+
+	// directories that show up get watched too
+	fs::create_dir_all(root.join("new/nested")).unwrap();
+	fs::write(root.join("new/nested/b"), vec![0; 500]).unwrap();
+	eventually(index, 2000);
+	fs::hard_link(root.join("new/nested/b"), root.join("new/b_again")).unwrap();
+
+	fs::rename(root.join("new"), root.join("moved")).unwrap();
+	fs::write(root.join("moved/nested/c"), vec![0; 100]).unwrap();
+	eventually(index, 2100);
+	let usage = index.usage(5);
+	assert_eq!(usage.top_dirs, vec![(root.join("logs"), 1500), (root.join("moved"), 600)]);
+
+	fs::remove_dir_all(root.join("logs")).unwrap();
+	eventually(index, 600);
+	assert_eq!(index.reconcile(), 0);
+
+	fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn stays_readable_while_reconciling() {
+	let root = scratch_dir("index_reconcile");
+	for i in 0..20 {
+		fs::write(root.join(i.to_string()), [0; 10]).unwrap();
+	}
+	let options = WalkOptions {
+		entries_per_sec: Some(40),
+		..Default::default()
+	};
+	let index = SizeIndex::watch(&root, &options).unwrap();
+
+	// takes half a second at that pace
+	let reconciling = {
+		let index = index.clone();
+		std::thread::spawn(move || index.reconcile())
+	};
+	std::thread::sleep(Duration::from_millis(100));
+	let started = Instant::now();
+	assert_eq!(index.usage(0).apparent, 200);
+	assert!(started.elapsed() < Duration::from_millis(100), "waited on the rescan for {:?}", started.elapsed());
+
+	// whether or not the rescan gets to see it, it's not lost when swapped in
+	fs::write(root.join("late"), [0; 50]).unwrap();
+	eventually(&index, 250);
+	reconciling.join().unwrap();
+	eventually(&index, 250);
+
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn stays_readable_while_applying_changes() {
+	let root = scratch_dir("index_moved_in");
+	let outside = scratch_dir("index_moved_in_outside");
+	fs::create_dir_all(outside.join("unpacked")).unwrap();
+	for i in 0..100 {
+		fs::write(outside.join("unpacked").join(i.to_string()), [0; 10]).unwrap();
+	}
+	let options = WalkOptions {
+		entries_per_sec: Some(40),
+		..Default::default()
+	};
+	let index = SizeIndex::watch(&root, &options).unwrap();
+
+	// takes a couple of seconds at that pace
+	fs::rename(outside.join("unpacked"), root.join("unpacked")).unwrap();
+	std::thread::sleep(Duration::from_millis(100));
+	let started = Instant::now();
+	assert_eq!(index.usage(0).apparent, 0);
+	assert!(started.elapsed() < Duration::from_millis(100), "waited on the scan for {:?}", started.elapsed());
+	eventually(&index, 1000);
+
+	fs::remove_dir_all(&root).unwrap();
+	fs::remove_dir_all(&outside).unwrap();
+}
+
+#[test]
+fn overlapping_reconciles_keep_changes() {
+	let root = scratch_dir("index_reconciles");
+	for i in 0..20 {
+		fs::write(root.join(i.to_string()), [0; 10]).unwrap();
+	}
+	let options = WalkOptions {
+		entries_per_sec: Some(40),
+		..Default::default()
+	};
+	let index = SizeIndex::watch(&root, &options).unwrap();
+
+	let reconcile = || {
+		let index = index.clone();
+		std::thread::spawn(move || index.reconcile())
+	};
+	let first = reconcile();
+	std::thread::sleep(Duration::from_millis(100));
+	fs::write(root.join("0"), [0; 60]).unwrap();
+	let second = reconcile();
+	std::thread::sleep(Duration::from_millis(100));
+	fs::write(root.join("late"), [0; 50]).unwrap();
+	first.join().unwrap();
+	second.join().unwrap();
+	eventually(&index, 300);
+	assert_eq!(index.reconcile(), 0);
+
+	fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use server_upkeep::{
+	checks::kernel::{Admission, Cooldowns},
+	kernel_log::{KernelEvent, parse_kmsg_record, read_export_entry},
+};
+
+#[test]
+fn recognizes_kernel_events() {
+	assert_eq!(
+		KernelEvent::parse("Out of memory: Killed process 4321 (java) total-vm:8123456kB, anon-rss:2097152kB, file-rss:1024kB, shmem-rss:0kB, UID:1000 pgtables:5000kB oom_score_adj:0"),
+		Some(KernelEvent::OomKill {
+			pid: 4321,
+			process: "java".to_owned(),
+			anon_rss: 2 * 1024 * 1024 * 1024,
+			file_rss: 1024 * 1024,
+			shmem_rss: 0,
+			cgroup: false,
+		})
+	);
+	assert!(matches!(
+		KernelEvent::parse("Memory cgroup out of memory: Killed process 77 (node (worker)) total-vm:100kB, anon-rss:50kB, file-rss:0kB, shmem-rss:0kB"),
+		Some(KernelEvent::OomKill { cgroup: true, ref process, .. }) if process == "node (worker)"
+	));
+	assert_eq!(
+		KernelEvent::parse("INFO: task kworker/0:1:123 blocked for more than 120 seconds."),
+		Some(KernelEvent::HungTask {
+			pid: 123,
+			process: "kworker/0:1".to_owned(),
+			secs: 120,
+		})
+	);
+	assert!(matches!(
+		KernelEvent::parse("EXT4-fs error (device sda1): ext4_find_entry:1455: inode #2: comm ls: reading directory lblock 0"),
+		Some(KernelEvent::FsError(_))
+	));
+	assert!(matches!(
+		KernelEvent::parse("blk_update_request: I/O error, dev sdb, sector 12345 op 0x0:(READ)"),
+		Some(KernelEvent::FsError(_))
+	));
+	assert!(
+		matches!(
+			KernelEvent::parse("XFS (sdc1): metadata I/O error in \"xfs_trans_read_buf_map\" at daddr 0x2 len 1 error 5, task ls blocked for more than a while"),
+			Some(KernelEvent::FsError(_))
+		),
+		"not quite a hung task, but still a filesystem error"
+	);
+	assert_eq!(KernelEvent::parse("usb 1-1: new high-speed USB device number 2 using xhci_hcd"), None);
+}
+
+#[test]
+fn parses_kmsg_records() {
+	assert_eq!(
+		parse_kmsg_record("6,1234,5678901,-;EXT4-fs (sda1): mounted filesystem\n SUBSYSTEM=block\n DEVICE=b8:1\n"),
+		Some("EXT4-fs (sda1): mounted filesystem")
+	);
+	assert_eq!(parse_kmsg_record("garbage"), None);
+}
+
+#[tokio::test]
+async fn reads_journal_export() {
+	let mut export = Vec::new();
+	export.extend_from_slice(b"__CURSOR=s=1\nMESSAGE=first\n_TRANSPORT=kernel\n\n");
+	// values with newlines or binary come length-prefixed
+	export.extend_from_slice(b"MESSAGE\n");
+	export.extend_from_slice(&14u64.to_le_bytes());
+	export.extend_from_slice(b"second\nmessage\n\n");
+	let mut reader = export.as_slice();
+
+	let first = read_export_entry(&mut reader).await.unwrap().unwrap();
+	assert_eq!(first["MESSAGE"], b"first");
+	assert_eq!(first["_TRANSPORT"], b"kernel");
+	let second = read_export_entry(&mut reader).await.unwrap().unwrap();
+	assert_eq!(second["MESSAGE"], b"second\nmessage");
+	assert!(read_export_entry(&mut reader).await.unwrap().is_none());
+}
+
+#[test]
+fn holds_back_within_cooldown() {
+	let start = Instant::now();
+	let at = |secs| start + Duration::from_secs(secs);
+	let mut cooldowns = Cooldowns::new(Duration::from_secs(300));
+
+	assert_eq!(cooldowns.admit("kernel:oom", "java (pid 1)".to_owned(), at(0)), Admission::Send { held_back: None });
+	cooldowns.alerted("kernel:oom", at(0));
+	assert_eq!(cooldowns.admit("kernel:oom", "java (pid 2)".to_owned(), at(10)), Admission::HeldBack);
+	assert_eq!(cooldowns.admit("kernel:oom", "node (pid 3)".to_owned(), at(20)), Admission::HeldBack);
+	assert_eq!(
+		cooldowns.admit("kernel:hung_task", "kworker (pid 4)".to_owned(), at(30)),
+		Admission::Send { held_back: None },
+		"cooldowns are per check"
+	);
+
+	// what was held back goes out on its own once the cooldown is over
+	assert_eq!(cooldowns.next_flush(), Some(at(300)));
+	assert!(cooldowns.due(at(299)).is_empty());
+	assert_eq!(cooldowns.due(at(300)), [("kernel:oom", "2 more since the last alert: java (pid 2), node (pid 3)".to_owned())]);
+	cooldowns.alerted("kernel:oom", at(300));
+	assert_eq!(cooldowns.next_flush(), None);
+
+	// or along with the next event, if that comes first
+	cooldowns.admit("kernel:oom", "java (pid 5)".to_owned(), at(310));
+	assert_eq!(
+		cooldowns.admit("kernel:oom", "java (pid 6)".to_owned(), at(600)),
+		Admission::Send {
+			held_back: Some("1 more since the last alert: java (pid 5)".to_owned())
+		}
+	);
+}
@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use server_upkeep::{alerts::Evaluation, checks::load::Window, notify::Severity};
+
+fn over_two(v: f64) -> Evaluation {
+	match v {
+		v if v >= 2.0 => Evaluation::Firing {
+			tier: 0,
+			severity: Severity::Warning,
+			threshold: Some("2x".to_owned()),
+		},
+		v if v < 1.0 => Evaluation::Clear { threshold: Some("1x".to_owned()) },
+		_ => Evaluation::Hold,
+	}
+}
+
+#[test]
+fn fires_only_on_sustained_breach() {
+	let start = Instant::now();
+	let at = |secs| start + Duration::from_secs(secs);
+	let mut window = Window::new(Duration::from_secs(600));
+
+	// over the tier from the start, but not yet for the whole window
+	for secs in (0..600).step_by(30) {
+		window.push(at(secs), 3.0);
+		assert!(matches!(window.sustained(at(secs), over_two), Evaluation::Hold));
+	}
+	window.push(at(600), 3.0);
+	assert!(matches!(window.sustained(at(600), over_two), Evaluation::Firing { .. }));
+
+	// a single dip doesn't clear it, nor does it keep firing on the level that no longer held throughout
+	window.push(at(630), 0.5);
+	assert!(matches!(window.sustained(at(630), over_two), Evaluation::Hold));
+
+	// a single spike in an otherwise quiet window doesn't fire
+	for secs in (660..=1230).step_by(30) {
+		window.push(at(secs), if secs == 900 { 5.0 } else { 0.5 });
+		assert!(!matches!(window.sustained(at(secs), over_two), Evaluation::Firing { .. }));
+	}
+	// cleared once the spike has left the window
+	window.push(at(1530), 0.5);
+	assert!(matches!(window.sustained(at(1530), over_two), Evaluation::Clear { .. }));
+}
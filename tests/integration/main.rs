@@ -1 +1,19 @@
-//TODO!!!: This. Should be the entry point to all integration tests of rust projects, following https://matklad.github.io/2021/02/27/delete-cargo-integration-tests.html
+//! Entry point to all integration tests of the crate, following https://matklad.github.io/2021/02/27/delete-cargo-integration-tests.html
+mod alerts;
+mod config;
+mod dirs;
+mod disk;
+mod email;
+mod http_stand_in;
+mod index;
+mod kernel_log;
+mod load;
+mod matrix;
+mod mounts;
+mod probe;
+mod proc;
+mod push;
+mod routing;
+mod systemd;
+mod walk;
+mod webhook;
@@ -0,0 +1,37 @@
+use reqwest::Client;
+use server_upkeep::{
+	config::MatrixConfig,
+	notify::{Alert, MatrixNotifier, Notifier, Severity},
+};
+
+use crate::http_stand_in::http_stand_in;
+
+#[tokio::test]
+async fn matrix_sends_formatted_message() {
+	let (addr, received) = http_stand_in(200, r#"{"event_id": "$stand_in"}"#).await;
+	let config = MatrixConfig {
+		homeserver: format!("http://{addr}"),
+		access_token: "secret".to_owned(),
+		room_id: "!room:localhost".to_owned(),
+		html: true,
+	};
+	let notifier = MatrixNotifier::new(Client::new(), config);
+
+	notifier
+		.send(&Alert::new("disk:/", Severity::Critical, "91%", "90%", "/ disk usage at 91% <root>"))
+		.await
+		.unwrap();
+
+	let request = received.await.unwrap();
+	assert_eq!(request.method, "PUT");
+	assert!(request.path.starts_with("/_matrix/client/v3/rooms/!room:localhost/send/m.room.message/"));
+	assert_eq!(request.headers["authorization"], "Bearer secret");
+
+	let content: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+	assert_eq!(content["msgtype"], "m.text");
+	assert!(content["body"].as_str().unwrap().ends_with("/ disk usage at 91% <root>"));
+	assert_eq!(content["format"], "org.matrix.custom.html");
+	let html = content["formatted_body"].as_str().unwrap();
+	assert!(html.contains("<b>CRITICAL</b>"));
+	assert!(html.contains("/ disk usage at 91% &lt;root&gt;"));
+}
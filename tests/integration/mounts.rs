@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use server_upkeep::mounts::parse_mountinfo;
+
+#[test]
+fn parses_mountinfo() {
+	let mountinfo = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw
+98 22 259:3 / /mnt/backup\\040disk rw,relatime shared:40 master:3 - xfs /dev/nvme0n1p3 rw,attr2
+99 22 259:2 /srv/data /data rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+100 22 0:55 / /mnt/share rw,relatime shared:50 - nfs4 server:/export rw
+101 22 0:56 / /mnt/remote rw,relatime shared:51 - fuse.sshfs user@host:/ rw
+garbage line
+";
+	let mounts = parse_mountinfo(mountinfo);
+
+	assert_eq!(mounts.len(), 6);
+	assert_eq!(mounts[0].mount_point, PathBuf::from("/"));
+	assert_eq!(mounts[0].device, "259:2");
+	assert!(!mounts[0].is_pseudo());
+	assert!(mounts[1].is_pseudo());
+	assert_eq!(mounts[2].mount_point, PathBuf::from("/mnt/backup disk"));
+	assert_eq!(mounts[2].fs_type, "xfs");
+	assert_eq!(mounts[2].source, "/dev/nvme0n1p3");
+	assert_eq!(mounts[3].root, "/srv/data");
+	assert!(!mounts[0].is_network());
+	assert!(mounts[4].is_network());
+	assert!(mounts[5].is_network(), "FUSE");
+}
@@ -0,0 +1,108 @@
+use std::{net::SocketAddr, time::Duration};
+
+use color_eyre::eyre::eyre;
+use regex::Regex;
+use server_upkeep::{
+	checks::endpoints::check_endpoint,
+	config::Endpoint,
+	notify::Severity,
+	probe::{Probe, client},
+};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::TcpListener,
+};
+
+use crate::{alerts::Recorder, http_stand_in::http_stand_in, walk::scratch_dir};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn http(addr: SocketAddr, status: Option<u16>, body: Option<&str>) -> Probe {
+	Probe::Http {
+		url: format!("http://{addr}/health"),
+		status,
+		body: body.map(|b| Regex::new(b).unwrap()),
+	}
+}
+
+#[tokio::test]
+async fn probes_http() {
+	let client = client().unwrap();
+
+	let (addr, received) = http_stand_in(200, r#"{"status": "ok"}"#).await;
+	let latency = http(addr, None, Some(r#""status":\s*"ok""#)).run(&client, TIMEOUT).await.unwrap();
+	assert!(latency < TIMEOUT);
+	let request = received.await.unwrap();
+	assert_eq!((request.method.as_str(), request.path.as_str()), ("GET", "/health"));
+
+	let (addr, _) = http_stand_in(503, "{}").await;
+	let e = http(addr, None, None).run(&client, TIMEOUT).await.unwrap_err();
+	assert!(e.to_string().contains("503"), "{e}");
+
+	let (addr, _) = http_stand_in(200, "{}").await;
+	assert!(http(addr, Some(204), None).run(&client, TIMEOUT).await.is_err(), "status other than the expected one");
+
+	let (addr, _) = http_stand_in(200, r#"{"status": "degraded"}"#).await;
+	assert!(http(addr, None, Some(r#""status":\s*"ok""#)).run(&client, TIMEOUT).await.is_err(), "body not matching");
+}
+
+#[tokio::test]
+async fn probes_tcp() {
+	let client = client().unwrap();
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	assert!(Probe::Tcp(addr.to_string()).run(&client, TIMEOUT).await.is_ok());
+	drop(listener);
+	assert!(Probe::Tcp(addr.to_string()).run(&client, TIMEOUT).await.is_err(), "nothing listening anymore");
+}
+
+#[tokio::test]
+async fn does_not_follow_redirects() {
+	let (healthy, _) = http_stand_in(200, "{}").await;
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		let (mut stream, _) = listener.accept().await.unwrap();
+		let _ = stream.read(&mut [0; 1024]).await;
+		let response = format!("HTTP/1.1 302 Found\r\nLocation: http://{healthy}/health\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+		stream.write_all(response.as_bytes()).await.unwrap();
+	});
+
+	let e = http(addr, None, None).run(&client().unwrap(), TIMEOUT).await.unwrap_err();
+	assert!(e.to_string().contains("302"), "{e}");
+}
+
+#[tokio::test]
+async fn alerts_after_consecutive_failures() {
+	let mut recorder = Recorder::new(&scratch_dir("endpoint_failures").join("alerts.json")).await;
+	let endpoint = Endpoint {
+		name: "api".to_owned(),
+		url: "http://localhost:8080/health".to_owned(),
+		status: None,
+		body: None,
+		timeout: TIMEOUT,
+		severity: Severity::Critical,
+		failures: 2,
+		max_latency: None,
+		latency_severity: Severity::Warning,
+	};
+	let down = || Err(eyre!("request failed"));
+	let up = || Ok(Duration::from_millis(5));
+
+	// a blip: one failure, then back up
+	check_endpoint(&recorder.alerter, &endpoint, down(), 1).await;
+	check_endpoint(&recorder.alerter, &endpoint, up(), 0).await;
+	assert!(recorder.sent().is_empty());
+
+	check_endpoint(&recorder.alerter, &endpoint, down(), 1).await;
+	assert!(recorder.sent().is_empty());
+	check_endpoint(&recorder.alerter, &endpoint, down(), 2).await;
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("endpoint:api")));
+	assert_eq!(fired["message"], "api is down\nhttp://localhost:8080/health: request failed");
+
+	check_endpoint(&recorder.alerter, &endpoint, up(), 0).await;
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!(resolved["status"], "resolved");
+}
@@ -0,0 +1,149 @@
+use std::{fs, process::Command, time::Instant};
+
+use regex::Regex;
+use server_upkeep::{
+	checks::processes::{CpuSamples, check_process},
+	config::ProcessRule,
+	notify::Severity,
+	proc::{MemInfo, PressureStats, ProcessMatcher, ProcessStat, page_size, parse_loadavg, parse_meminfo, parse_pressure, parse_stat},
+};
+
+use crate::{alerts::Recorder, walk::scratch_dir};
+
+#[test]
+fn parses_meminfo() {
+	let meminfo = "\
+MemTotal:       16000000 kB
+MemFree:          500000 kB
+MemAvailable:    4000000 kB
+Buffers:          100000 kB
+SwapTotal:       2000000 kB
+SwapFree:        1500000 kB
+";
+	let parsed = parse_meminfo(meminfo).unwrap();
+
+	assert_eq!(
+		parsed,
+		MemInfo {
+			total: 16_000_000 * 1024,
+			available: 4_000_000 * 1024,
+			swap_total: 2_000_000 * 1024,
+			swap_free: 1_500_000 * 1024,
+		}
+	);
+	assert_eq!(parsed.used_pct(), 75);
+	assert_eq!(parsed.swap_used_pct(), Some(25));
+	assert!(parse_meminfo("MemTotal: 1 kB\n").is_err());
+}
+
+#[test]
+fn parses_pressure() {
+	let pressure = "\
+some avg10=1.50 avg60=12.34 avg300=0.87 total=12345678
+full avg10=0.00 avg60=3.00 avg300=0.10 total=345
+";
+	let parsed = parse_pressure(pressure).unwrap();
+
+	assert_eq!(
+		parsed.some,
+		PressureStats {
+			avg10: 1.5,
+			avg60: 12.34,
+			avg300: 0.87,
+			total: 12_345_678,
+		}
+	);
+	assert_eq!(parsed.full.unwrap().avg60, 3.0);
+	assert!(parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").unwrap().full.is_none());
+}
+
+#[test]
+fn parses_loadavg() {
+	let loadavg = parse_loadavg("0.52 1.58 12.00 3/1163 12345\n").unwrap();
+
+	assert_eq!((loadavg.one, loadavg.five, loadavg.fifteen), (0.52, 1.58, 12.0));
+	assert!(parse_loadavg("garbage").is_err());
+}
+
+#[test]
+fn parses_stat() {
+	let stat = "4321 (weird) name) S 1 4321 4321 0 -1 4194560 900 0 0 0 150 25 0 0 20 0 3 0 12345 987654321 2048 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0\n";
+	let parsed = parse_stat(stat).unwrap();
+
+	assert_eq!((parsed.pid, parsed.comm.as_str()), (4321, "weird) name"));
+	assert_eq!(parsed.cpu_ticks, 175);
+	assert_eq!(parsed.rss, 2048 * page_size());
+	assert!(parse_stat("4321 (truncated) S 1").is_err());
+}
+
+#[test]
+fn finds_processes() {
+	let dir = scratch_dir("processes");
+	let mut child = Command::new("sleep").arg("1234.5").spawn().unwrap();
+	let pid = child.id();
+	let pidfile = dir.join("sleep.pid");
+	fs::write(&pidfile, format!("{pid}\n")).unwrap();
+
+	let pids = |matcher: ProcessMatcher| matcher.find().unwrap().into_iter().map(|p| p.pid).collect::<Vec<_>>();
+	assert!(pids(ProcessMatcher::Name("sleep".to_owned())).contains(&pid));
+	assert_eq!(pids(ProcessMatcher::Cmdline(Regex::new(r"^sleep 1234\.5$").unwrap())), [pid]);
+	assert_eq!(pids(ProcessMatcher::Pidfile(pidfile.clone())), [pid]);
+	let found = ProcessMatcher::Pidfile(pidfile.clone()).find().unwrap();
+	assert_eq!(found[0].cmdline, ["sleep", "1234.5"]);
+	assert!(found[0].fds.is_some_and(|fds| fds > 0));
+
+	let matchers = [
+		ProcessMatcher::Cmdline(Regex::new(r"^sleep 1234\.5$").unwrap()),
+		ProcessMatcher::Pidfile(dir.join("missing.pid")),
+		ProcessMatcher::Cmdline(Regex::new(r"^nothing runs this$").unwrap()),
+		ProcessMatcher::Pidfile(dir.clone()),
+	];
+	let [sleep, missing, nothing, unreadable] = ProcessMatcher::find_all(&matchers).unwrap().try_into().unwrap();
+	let sleep = sleep.unwrap();
+	assert_eq!(sleep.iter().map(|p| p.pid).collect::<Vec<_>>(), [pid]);
+	assert!(sleep[0].fds.is_some_and(|fds| fds > 0));
+	assert!(missing.unwrap().is_empty() && nothing.unwrap().is_empty());
+	assert!(unreadable.is_err(), "a directory as the pidfile");
+
+	child.kill().unwrap();
+	child.wait().unwrap();
+	assert!(pids(ProcessMatcher::Pidfile(pidfile)).is_empty(), "stale pidfile");
+	assert!(pids(ProcessMatcher::Pidfile(dir.join("missing.pid"))).is_empty());
+}
+
+#[tokio::test]
+async fn resolves_limits_once_gone() {
+	let mut recorder = Recorder::new(&scratch_dir("process_limits").join("alerts.json")).await;
+	let rule = ProcessRule {
+		name: "worker".to_owned(),
+		cmdline: None,
+		pidfile: None,
+		severity: Severity::Critical,
+		max_rss: None,
+		max_cpu: None,
+		max_fds: Some(100),
+		limit_severity: Severity::Warning,
+	};
+	let leaking = ProcessStat {
+		pid: 4321,
+		comm: "worker".to_owned(),
+		cmdline: vec!["worker".to_owned()],
+		fds: Some(500),
+		..Default::default()
+	};
+
+	check_process(&recorder.alerter, &rule, &[leaking], &CpuSamples::new(), Instant::now()).await;
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("process_fds:worker")));
+
+	check_process(&recorder.alerter, &rule, &[], &CpuSamples::new(), Instant::now()).await;
+	let sent = recorder.sent();
+	let [down, resolved] = sent.as_slice() else { panic!("{sent:?}") };
+	assert_eq!((down["status"].as_str(), down["check"].as_str()), (Some("firing"), Some("process:worker")));
+	assert_eq!((resolved["status"].as_str(), resolved["check"].as_str()), (Some("resolved"), Some("process_fds:worker")));
+	assert_eq!(resolved["message"], "worker open files back to none, not running after 0m (peaked at 500)");
+
+	// nothing left firing on the limit to resolve again
+	check_process(&recorder.alerter, &rule, &[], &CpuSamples::new(), Instant::now()).await;
+	assert!(recorder.sent().is_empty());
+}
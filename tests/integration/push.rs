@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use reqwest::Client;
+use server_upkeep::{
+	config::{GotifyConfig, NtfyConfig, SinkKind},
+	notify::{Alert, GotifyNotifier, Notifier, NtfyNotifier, Severity, Sink},
+};
+
+use crate::http_stand_in::http_stand_in;
+
+#[tokio::test]
+async fn ntfy_maps_severity_to_priority() {
+	let ntfy = |addr, priorities| {
+		NtfyNotifier::new(
+			Client::new(),
+			NtfyConfig {
+				server: format!("http://{addr}/"),
+				topic: "alerts".to_owned(),
+				token: Some("secret".to_owned()),
+				priorities,
+			},
+		)
+	};
+
+	let (addr, received) = http_stand_in(200, "{}").await;
+	ntfy(addr, BTreeMap::new())
+		.send(&Alert::new("disk:/", Severity::Emergency, "96%", "95%", "/ disk usage at 96%"))
+		.await
+		.unwrap();
+	let request = received.await.unwrap();
+	assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/alerts"));
+	assert_eq!(request.headers["priority"], "5");
+	assert_eq!(request.headers["tags"], "emergency");
+	assert_eq!(request.headers["authorization"], "Bearer secret");
+	assert!(request.headers["title"].ends_with(": disk:/"));
+	assert_eq!(request.body, "/ disk usage at 96%");
+
+	// resolutions are quiet, whatever they resolve
+	let (addr, received) = http_stand_in(200, "{}").await;
+	ntfy(addr, BTreeMap::from([(Severity::Info, 1)]))
+		.send(&Alert::new("disk:/", Severity::Emergency, "40%", "45%", "/ disk usage back to 40%").resolved())
+		.await
+		.unwrap();
+	let request = received.await.unwrap();
+	assert_eq!(request.headers["priority"], "1");
+	assert_eq!(request.headers["tags"], "white_check_mark");
+}
+
+#[tokio::test]
+async fn gotify_maps_severity_to_priority() {
+	let (addr, received) = http_stand_in(200, "{}").await;
+	let gotify = GotifyNotifier::new(
+		Client::new(),
+		GotifyConfig {
+			server: format!("http://{addr}"),
+			app_token: "secret".to_owned(),
+			priorities: BTreeMap::from([(Severity::Warning, 6)]),
+		},
+	);
+
+	gotify.send(&Alert::new("memory", Severity::Warning, "91%", "90%", "memory usage at 91%")).await.unwrap();
+
+	let request = received.await.unwrap();
+	assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/message"));
+	assert_eq!(request.headers["x-gotify-key"], "secret");
+	let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+	assert_eq!(body["priority"], 6, "overridden");
+	assert_eq!(body["message"], "memory usage at 91%");
+	assert!(body["title"].as_str().unwrap().ends_with(": memory"));
+}
+
+#[test]
+fn rejects_out_of_range_priorities() {
+	let ntfy = |priority| {
+		SinkKind::Ntfy(NtfyConfig {
+			server: "http://localhost".to_owned(),
+			topic: "alerts".to_owned(),
+			token: None,
+			priorities: BTreeMap::from([(Severity::Critical, priority)]),
+		})
+	};
+	let gotify = |priority| {
+		SinkKind::Gotify(GotifyConfig {
+			server: "http://localhost".to_owned(),
+			app_token: "secret".to_owned(),
+			priorities: BTreeMap::from([(Severity::Critical, priority)]),
+		})
+	};
+	let client = Client::new();
+
+	assert!(Sink::from_config(&ntfy(5), &client).is_ok());
+	assert!(Sink::from_config(&ntfy(0), &client).is_err());
+	assert!(Sink::from_config(&ntfy(6), &client).is_err());
+	assert!(Sink::from_config(&gotify(0), &client).is_ok());
+	assert!(Sink::from_config(&gotify(11), &client).is_err());
+}
@@ -0,0 +1,143 @@
+use chrono::NaiveTime;
+use server_upkeep::{
+	config::{AlertsConfig, RouteConfig, SinkConfig, SinkKind, TelegramConfig},
+	notify::{
+		Alert, Severity,
+		routing::{Route, parse_time_window},
+	},
+};
+
+fn sink_names() -> Vec<String> {
+	vec!["chat".to_owned(), "pager".to_owned()]
+}
+
+fn route(config: RouteConfig) -> Route {
+	Route::from_config(&config, &sink_names()).unwrap()
+}
+
+fn to(sinks: &[&str]) -> RouteConfig {
+	RouteConfig {
+		checks: Vec::new(),
+		min_severity: None,
+		host_tags: Vec::new(),
+		between: None,
+		sinks: sinks.iter().map(|s| (*s).to_owned()).collect(),
+	}
+}
+
+fn at(h: u32, m: u32) -> NaiveTime {
+	NaiveTime::from_hms_opt(h, m, 0).unwrap()
+}
+
+fn alert(check: &str, severity: Severity) -> Alert {
+	Alert::new(check, severity, "", "", "")
+}
+
+#[test]
+fn matches_time_windows() {
+	assert_eq!(parse_time_window("09:00 - 17:30").unwrap(), (at(9, 0), at(17, 30)));
+	assert!(parse_time_window("09:00").is_err());
+	assert!(parse_time_window("25:00-08:00").is_err());
+
+	let night = route(RouteConfig {
+		between: Some("22:00-08:00".to_owned()),
+		..to(&["pager"])
+	});
+	let any = alert("disk:/", Severity::Info);
+	assert!(night.matches(&any, &[], at(23, 0)));
+	assert!(night.matches(&any, &[], at(0, 0)));
+	assert!(night.matches(&any, &[], at(7, 59)));
+	assert!(!night.matches(&any, &[], at(8, 0)), "end is exclusive");
+	assert!(!night.matches(&any, &[], at(12, 0)));
+	assert!(night.matches(&any, &[], at(22, 0)));
+
+	let day = route(RouteConfig {
+		between: Some("08:00-22:00".to_owned()),
+		..to(&["chat"])
+	});
+	assert!(day.matches(&any, &[], at(12, 0)));
+	assert!(!day.matches(&any, &[], at(23, 0)));
+}
+
+#[test]
+fn matches_checks_severity_and_tags() {
+	let disks = route(RouteConfig {
+		checks: vec!["disk:*".to_owned(), "memory".to_owned()],
+		..to(&["chat"])
+	});
+	let noon = at(12, 0);
+	assert!(disks.matches(&alert("disk:/data", Severity::Info), &[], noon));
+	assert!(disks.matches(&alert("memory", Severity::Info), &[], noon));
+	assert!(!disks.matches(&alert("memory_pressure", Severity::Info), &[], noon), "no prefix match without `*`");
+	assert!(!disks.matches(&alert("inode:/", Severity::Info), &[], noon));
+
+	let critical = route(RouteConfig {
+		min_severity: Some(Severity::Critical),
+		..to(&["pager"])
+	});
+	assert!(!critical.matches(&alert("disk:/", Severity::Warning), &[], noon));
+	assert!(critical.matches(&alert("disk:/", Severity::Critical), &[], noon));
+	assert!(critical.matches(&alert("disk:/", Severity::Emergency), &[], noon));
+
+	let prod_db = route(RouteConfig {
+		host_tags: vec!["prod".to_owned(), "db".to_owned()],
+		..to(&["pager"])
+	});
+	let tags = |tags: &[&str]| tags.iter().map(|t| (*t).to_owned()).collect::<Vec<_>>();
+	assert!(prod_db.matches(&alert("disk:/", Severity::Info), &tags(&["db", "eu", "prod"]), noon));
+	assert!(!prod_db.matches(&alert("disk:/", Severity::Info), &tags(&["prod"]), noon), "every tag of the route is required");
+}
+
+#[test]
+fn first_matching_route_wins() {
+	let routes = [
+		route(RouteConfig {
+			checks: vec!["disk:*".to_owned()],
+			min_severity: Some(Severity::Critical),
+			..to(&["chat", "pager"])
+		}),
+		route(RouteConfig {
+			checks: vec!["disk:*".to_owned()],
+			..to(&["chat"])
+		}),
+		route(to(&["pager"])),
+	];
+	let pick = |alert: &Alert| routes.iter().find(|r| r.matches(alert, &[], at(12, 0))).unwrap().sinks.clone();
+
+	assert_eq!(pick(&alert("disk:/", Severity::Critical)), [0, 1]);
+	assert_eq!(pick(&alert("disk:/", Severity::Warning)), [0]);
+	assert_eq!(pick(&alert("memory", Severity::Critical)), [1]);
+	assert!(Route::from_config(&to(&["nowhere"]), &sink_names()).is_err());
+}
+
+#[test]
+fn validates_sinks_and_routes() {
+	let telegram = |name: Option<&str>| SinkConfig {
+		name: name.map(str::to_owned),
+		kind: SinkKind::Telegram(TelegramConfig::default()),
+	};
+
+	let config = AlertsConfig {
+		sinks: vec![telegram(None), telegram(None)],
+		..Default::default()
+	};
+	assert!(config.validate().is_err(), "both default to the name `telegram`");
+
+	let config = AlertsConfig {
+		sinks: vec![telegram(Some("ops")), telegram(Some("ops"))],
+		..Default::default()
+	};
+	assert!(config.validate().is_err());
+
+	let mut config = AlertsConfig {
+		sinks: vec![telegram(None), telegram(Some("pager"))],
+		routes: vec![RouteConfig {
+			min_severity: Some(Severity::Critical),
+			..to(&["pager"])
+		}],
+		..Default::default()
+	};
+	assert!(config.validate().is_err(), "alerts below critical would go nowhere");
+	config.routes.push(to(&["telegram"]));
+	assert!(config.validate().is_ok());
+}
@@ -0,0 +1,262 @@
+use std::{
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicU32, Ordering},
+	},
+	time::Duration,
+};
+
+use server_upkeep::{
+	checks::systemd::{RestartHistory, check_units},
+	config::MonitorConfig,
+	systemd::{Systemd, Unit, UnitStatus},
+};
+use tokio::net::UnixStream;
+use zbus::{
+	Guid, connection, interface,
+	zvariant::{ObjectPath, OwnedObjectPath},
+};
+
+use crate::{alerts::Recorder, walk::scratch_dir};
+
+const NGINX_PATH: &str = "/org/freedesktop/systemd1/unit/nginx_2eservice";
+const BACKUP_PATH: &str = "/org/freedesktop/systemd1/unit/backup_2eservice";
+
+/// Unit as the mock lists it, which tests change between rounds
+#[derive(Clone)]
+struct MockUnit {
+	name: &'static str,
+	active: &'static str,
+	sub: &'static str,
+	path: &'static str,
+}
+
+#[derive(Clone, Default)]
+struct MockUnits(Arc<Mutex<Vec<MockUnit>>>);
+
+impl MockUnits {
+	fn set(&self, name: &'static str, active: &'static str, sub: &'static str) {
+		let mut units = self.0.lock().unwrap();
+		match units.iter_mut().find(|u| u.name == name) {
+			Some(unit) => (unit.active, unit.sub) = (active, sub),
+			None => {
+				let path = match name {
+					"nginx.service" => NGINX_PATH,
+					_ => BACKUP_PATH,
+				};
+				units.push(MockUnit { name, active, sub, path });
+			}
+		}
+	}
+
+	fn remove(&self, name: &str) {
+		self.0.lock().unwrap().retain(|u| u.name != name);
+	}
+}
+
+struct MockManager {
+	units: MockUnits,
+}
+
+#[interface(name = "org.freedesktop.systemd1.Manager")]
+impl MockManager {
+	fn list_units(&self) -> Vec<UnitStatus> {
+		let no_job = OwnedObjectPath::from(ObjectPath::from_static_str_unchecked("/"));
+		let units = self.units.0.lock().unwrap();
+		units
+			.iter()
+			.map(|unit| {
+				(
+					unit.name.to_owned(),
+					String::new(),
+					"loaded".to_owned(),
+					unit.active.to_owned(),
+					unit.sub.to_owned(),
+					String::new(),
+					OwnedObjectPath::from(ObjectPath::from_static_str_unchecked(unit.path)),
+					0,
+					String::new(),
+					no_job.clone(),
+				)
+			})
+			.collect()
+	}
+}
+
+#[derive(Clone, Default)]
+struct MockService {
+	restarts: Arc<AtomicU32>,
+}
+
+#[interface(name = "org.freedesktop.systemd1.Service")]
+impl MockService {
+	#[zbus(property, name = "NRestarts")]
+	fn n_restarts(&self) -> u32 {
+		self.restarts.load(Ordering::SeqCst)
+	}
+}
+
+struct MockSystemd {
+	_server: connection::Connection,
+	systemd: Systemd,
+	units: MockUnits,
+	nginx: MockService,
+}
+
+/// Serves the mocked systemd API on one end of a socket pair, private to the test, and connects to it from the other.
+/// Starts out with nginx restart-looping and backup failed.
+async fn mock_systemd() -> MockSystemd {
+	let units = MockUnits::default();
+	units.set("nginx.service", "activating", "auto-restart");
+	units.set("backup.service", "failed", "failed");
+	let (nginx, backup) = (MockService::default(), MockService::default());
+	nginx.restarts.store(7, Ordering::SeqCst);
+
+	let (server, client) = UnixStream::pair().unwrap();
+	let server = connection::Builder::unix_stream(server)
+		.server(Guid::generate())
+		.unwrap()
+		.p2p()
+		.serve_at("/org/freedesktop/systemd1", MockManager { units: units.clone() })
+		.unwrap()
+		.serve_at(NGINX_PATH, nginx.clone())
+		.unwrap()
+		.serve_at(BACKUP_PATH, backup)
+		.unwrap()
+		.build();
+	let client = connection::Builder::unix_stream(client).p2p().build();
+	let (server, client) = tokio::try_join!(server, client).unwrap();
+	MockSystemd {
+		_server: server,
+		systemd: Systemd::with_connection(client).await.unwrap(),
+		units,
+		nginx,
+	}
+}
+
+fn config(restart_window: Duration) -> MonitorConfig {
+	MonitorConfig {
+		systemd_restart_threshold: 3,
+		systemd_restart_window: restart_window,
+		// no journal to read from in tests
+		systemd_journal_lines: 0,
+		..Default::default()
+	}
+}
+
+#[tokio::test]
+async fn lists_units_and_restart_counts() {
+	let mock = mock_systemd().await;
+
+	let units = mock.systemd.units().await.unwrap();
+	assert_eq!(
+		units.iter().map(|u| (u.name.as_str(), u.active_state.as_str(), u.sub_state.as_str())).collect::<Vec<_>>(),
+		[("nginx.service", "activating", "auto-restart"), ("backup.service", "failed", "failed")]
+	);
+	assert!(units.iter().all(Unit::is_service));
+
+	assert_eq!(mock.systemd.n_restarts(&units[0]).await.unwrap(), 7);
+	assert_eq!(mock.systemd.n_restarts(&units[1]).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn alerts_on_failed_units_until_they_recover() {
+	let mock = mock_systemd().await;
+	mock.units.set("nginx.service", "active", "running");
+	let mut recorder = Recorder::new(&scratch_dir("systemd_failed").join("alerts.json")).await;
+	let config = config(Duration::from_secs(600));
+	let mut restarts = RestartHistory::new();
+
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("systemd:backup.service")));
+	assert_eq!(fired["message"], "backup.service is failed");
+
+	// still failed: nothing new to say
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	assert!(recorder.sent().is_empty());
+
+	mock.units.set("backup.service", "inactive", "dead");
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!((resolved["status"].as_str(), resolved["check"].as_str()), (Some("resolved"), Some("systemd:backup.service")));
+	assert_eq!(resolved["message"], "backup.service back to inactive after 0m");
+
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	assert!(recorder.sent().is_empty());
+}
+
+#[tokio::test]
+async fn resolves_failed_units_once_unloaded() {
+	let mock = mock_systemd().await;
+	mock.units.set("nginx.service", "active", "running");
+	let mut recorder = Recorder::new(&scratch_dir("systemd_unloaded").join("alerts.json")).await;
+	let config = config(Duration::from_secs(600));
+	let mut restarts = RestartHistory::new();
+
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!(fired["check"], "systemd:backup.service");
+
+	// what `systemctl reset-failed` does to a transient unit
+	mock.units.remove("backup.service");
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!((resolved["status"].as_str(), resolved["check"].as_str()), (Some("resolved"), Some("systemd:backup.service")));
+	assert_eq!(resolved["message"], "backup.service back to unloaded after 0m");
+}
+
+#[tokio::test]
+async fn detects_restart_loops_within_the_window() {
+	let mock = mock_systemd().await;
+	mock.units.set("backup.service", "active", "running");
+	let mut recorder = Recorder::new(&scratch_dir("systemd_restarts").join("alerts.json")).await;
+	let window = Duration::from_secs(1);
+	let config = config(window);
+	let mut restarts = RestartHistory::new();
+
+	// the first reading is only the baseline, whatever restarts came before it
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	assert!(recorder.sent().is_empty());
+
+	mock.nginx.restarts.fetch_add(2, Ordering::SeqCst);
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	assert!(recorder.sent().is_empty());
+
+	mock.nginx.restarts.fetch_add(1, Ordering::SeqCst);
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("systemd_restarts:nginx.service")));
+	assert_eq!(fired["message"], "nginx.service restarts at 3 in 0m (crossed 3 in 0m threshold)");
+
+	// it recovered, and the restarts fell out of the window
+	mock.units.set("nginx.service", "active", "running");
+	tokio::time::sleep(window).await;
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	let [resolved] = recorder.sent().try_into().unwrap();
+	assert_eq!(
+		(resolved["status"].as_str(), resolved["check"].as_str()),
+		(Some("resolved"), Some("systemd_restarts:nginx.service"))
+	);
+}
+
+#[tokio::test]
+async fn detects_restart_loops_of_units_never_seen_restarting() {
+	let mock = mock_systemd().await;
+	// crashing a while after each start, so every poll happens to find it up
+	mock.units.set("nginx.service", "active", "running");
+	mock.units.set("backup.service", "active", "running");
+	let mut recorder = Recorder::new(&scratch_dir("systemd_restarts_running").join("alerts.json")).await;
+	let config = config(Duration::from_secs(600));
+	let mut restarts = RestartHistory::new();
+
+	for _ in 0..3 {
+		check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+		assert!(recorder.sent().is_empty());
+		mock.nginx.restarts.fetch_add(1, Ordering::SeqCst);
+	}
+	check_units(&config, &recorder.alerter, &mock.systemd, &mut restarts).await.unwrap();
+	let [fired] = recorder.sent().try_into().unwrap();
+	assert_eq!((fired["status"].as_str(), fired["check"].as_str()), (Some("firing"), Some("systemd_restarts:nginx.service")));
+	assert_eq!(fired["message"], "nginx.service restarts at 3 in 10m (crossed 3 in 10m threshold)");
+}
@@ -0,0 +1,113 @@
+use std::{
+	fs,
+	os::unix::fs::{MetadataExt, symlink},
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+use globset::{Glob, GlobSetBuilder};
+use server_upkeep::{
+	config::ThrottleConfig,
+	walk::{WalkOptions, dir_usage, walk_pool},
+};
+
+pub fn scratch_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("server_upkeep_test_{name}_{}", std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+#[test]
+fn finds_top_consumers() {
+	let root = scratch_dir("top_consumers");
+	fs::create_dir_all(root.join("big/nested")).unwrap();
+	fs::create_dir_all(root.join("small")).unwrap();
+	fs::write(root.join("big/nested/a"), vec![0; 3000]).unwrap();
+	fs::write(root.join("big/b"), vec![0; 2000]).unwrap();
+	fs::write(root.join("small/c"), vec![0; 100]).unwrap();
+	fs::write(root.join("top_level"), vec![0; 500]).unwrap();
+	// not followed, so counted as the link itself rather than the whole tree again
+	symlink(&root, root.join("loop")).unwrap();
+
+	let options = WalkOptions { top_n: 2, ..Default::default() };
+	let usage = dir_usage(&root, &options).unwrap();
+
+	let link_size = fs::symlink_metadata(root.join("loop")).unwrap().len();
+	assert_eq!(usage.apparent, 5600 + link_size);
+	assert_eq!(usage.top_dirs, vec![(root.join("big"), 5000), (root.join("small"), 100)]);
+	assert_eq!(usage.top_files, vec![(root.join("big/nested/a"), 3000), (root.join("big/b"), 2000)]);
+
+	let pool = walk_pool(2, &ThrottleConfig::default()).unwrap();
+	for _ in 0..2 {
+		let options = WalkOptions {
+			top_n: 2,
+			pool: Some(pool.clone()),
+			..Default::default()
+		};
+		assert_eq!(dir_usage(&root, &options).unwrap().top_dirs, usage.top_dirs, "same on a shared pool, walk after walk");
+	}
+
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn skips_excluded() {
+	let root = scratch_dir("excluded");
+	fs::create_dir_all(root.join("cache/deep")).unwrap();
+	fs::create_dir_all(root.join("logs")).unwrap();
+	fs::write(root.join("cache/deep/blob"), vec![0; 4000]).unwrap();
+	fs::write(root.join("logs/app.log"), vec![0; 1000]).unwrap();
+	fs::write(root.join("logs/app.db"), vec![0; 300]).unwrap();
+	fs::write(root.join("kept"), vec![0; 200]).unwrap();
+
+	let mut exclude = GlobSetBuilder::new();
+	exclude.add(Glob::new("cache").unwrap());
+	exclude.add(Glob::new("*.log").unwrap());
+	let options = WalkOptions {
+		exclude: exclude.build().unwrap(),
+		..Default::default()
+	};
+	let usage = dir_usage(&root, &options).unwrap();
+
+	assert_eq!(usage.apparent, 500);
+
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn counts_hardlinks_once() {
+	let root = scratch_dir("hardlinks");
+	fs::create_dir_all(root.join("a")).unwrap();
+	fs::create_dir_all(root.join("b")).unwrap();
+	fs::write(root.join("a/data"), vec![1; 4000]).unwrap();
+	fs::hard_link(root.join("a/data"), root.join("b/data")).unwrap();
+	fs::hard_link(root.join("a/data"), root.join("again")).unwrap();
+
+	let usage = dir_usage(&root, &WalkOptions::default()).unwrap();
+
+	assert_eq!(usage.apparent, 4000);
+	assert_eq!(usage.allocated, fs::metadata(root.join("again")).unwrap().blocks() * 512);
+
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn paces_entries() {
+	let root = scratch_dir("paced");
+	for i in 0..10 {
+		fs::write(root.join(i.to_string()), [0]).unwrap();
+	}
+
+	let options = WalkOptions {
+		entries_per_sec: Some(100),
+		..Default::default()
+	};
+	let started = Instant::now();
+	let usage = dir_usage(&root, &options).unwrap();
+
+	assert_eq!(usage.apparent, 10);
+	assert!(started.elapsed() >= Duration::from_millis(90), "10 entries at 100/s took only {:?}", started.elapsed());
+
+	fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+use reqwest::Client;
+use server_upkeep::{
+	config::WebhookConfig,
+	notify::{Alert, Notifier, Severity, WebhookNotifier, render_template},
+};
+
+use crate::http_stand_in::http_stand_in;
+
+#[test]
+fn renders_template() {
+	let mut alert = Alert::new("disk:/", Severity::Critical, "91%", "90%", "line one\nsaid \"full\" \\ {{check}}");
+	alert.host = "web-{{severity}}".to_owned();
+
+	let rendered = render_template(r#"{"text": "{{message}}", "host": "{{host}}", "status": "{{status}}", "keep": "{{unknown}} {{"}"#, &alert);
+	let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+	assert_eq!(parsed["text"], "line one\nsaid \"full\" \\ {{check}}", "escaped, and placeholders in values left alone");
+	assert_eq!(parsed["host"], "web-{{severity}}");
+	assert_eq!(parsed["status"], "firing");
+	assert_eq!(parsed["keep"], "{{unknown}} {{");
+
+	assert_eq!(render_template("{{status}}", &alert.resolved()), "resolved");
+}
+
+#[tokio::test]
+async fn webhook_sends_rendered_body() {
+	let (addr, received) = http_stand_in(200, "{}").await;
+	let config = WebhookConfig {
+		url: format!("http://{addr}/hooks/alerts"),
+		method: "put".to_owned(),
+		headers: BTreeMap::from([("X-Token".to_owned(), "secret".to_owned())]),
+		body: r#"{"check": "{{check}}", "severity": "{{severity}}", "value": "{{value}}", "threshold": "{{threshold}}"}"#.to_owned(),
+	};
+	let notifier = WebhookNotifier::new(Client::new(), &config).unwrap();
+
+	notifier.send(&Alert::new("disk:/", Severity::Critical, "91%", "90%", "/ disk usage at 91%")).await.unwrap();
+
+	let request = received.await.unwrap();
+	assert_eq!((request.method.as_str(), request.path.as_str()), ("PUT", "/hooks/alerts"));
+	assert_eq!(request.headers["x-token"], "secret");
+	assert_eq!(request.headers["content-type"], "application/json");
+	let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+	assert_eq!(body, serde_json::json!({"check": "disk:/", "severity": "critical", "value": "91%", "threshold": "90%"}));
+}